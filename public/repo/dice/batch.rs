@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::DiceError;
+
+/// What one finished photo looked like, for the summary table.
+pub struct JobOutcome {
+    pub output: PathBuf,
+    pub grid: (u32, u32),        // dice across, dice down
+    pub output_size: (u32, u32), // pixels
+}
+
+pub struct JobResult {
+    pub input: PathBuf,
+    pub outcome: Result<JobOutcome, DiceError>,
+    pub elapsed: Duration,
+}
+
+/// A directory or a glob pattern means batch mode, a plain file path doesn't. A file that exists is
+/// taken as it is, even with glob characters in its name like `IMG[1].jpg`.
+pub fn is_batch_input(input: &str) -> bool {
+    let path = Path::new(input);
+    if path.is_file() {
+        return false;
+    }
+    path.is_dir() || input.contains(['*', '?', '['])
+}
+
+/// Every image in a directory, or every image matching a glob, sorted by path.
+pub fn collect_inputs(input: &str) -> Result<Vec<PathBuf>, DiceError> {
+    let mut paths: Vec<PathBuf> = if Path::new(input).is_dir() {
+        std::fs::read_dir(input)
+            .map_err(|e| DiceError::io(input, e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect()
+    } else {
+        glob::glob(input)
+            .map_err(|e| DiceError::InvalidOptions(format!("Bad pattern {}: {}", input, e)))?
+            .filter_map(|entry| entry.ok())
+            .collect()
+    };
+
+    // Skip sidecar files, notes, grids and whatever else lives next to the photos
+    paths.retain(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok());
+    paths.sort();
+
+    if paths.is_empty() {
+        return Err(DiceError::InvalidOptions(format!("No images found in {}", input)));
+    }
+    Ok(paths)
+}
+
+/// Fills in the naming template for one input.
+///
+/// `{stem}` is the input name without extension, `{name}` the full file name, `{index}` the
+/// 1-based position in the batch and `{ext}` the extension of the output format.
+pub fn output_name(template: &str, input: &Path, index: usize, ext: &str) -> String {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let name = input.file_name().and_then(|s| s.to_str()).unwrap_or("image");
+    template
+        .replace("{stem}", stem)
+        .replace("{name}", name)
+        .replace("{index}", &index.to_string())
+        .replace("{ext}", ext)
+}
+
+/// The output names of the whole batch, in input order. Two inputs ending up with the same name
+/// (`a/IMG_1.jpg` and `b/IMG_1.jpg` with `{stem}`) is an error, the later one would overwrite the first.
+pub fn output_names(template: &str, inputs: &[PathBuf], ext: &str) -> Result<Vec<String>, DiceError> {
+    let names: Vec<String> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| output_name(template, input, index + 1, ext))
+        .collect();
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (index, name) in names.iter().enumerate() {
+        if let Some(first) = seen.insert(name, index) {
+            return Err(DiceError::InvalidOptions(format!(
+                "{} and {} would both be written to {}, put {{index}} in --name-template to tell them apart",
+                inputs[first].display(),
+                inputs[index].display(),
+                name
+            )));
+        }
+    }
+    Ok(names)
+}
+
+/// Runs `job` on every input with `workers` threads. Results come back in input order.
+pub fn run_batch<F>(inputs: &[PathBuf], workers: usize, job: F) -> Vec<JobResult>
+where
+    F: Fn(usize, &Path) -> Result<JobOutcome, DiceError> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<JobResult>>> = Mutex::new(inputs.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(index) else { break };
+
+                let started = Instant::now();
+                let outcome = job(index, input);
+                let elapsed = started.elapsed();
+
+                let finished = done.fetch_add(1, Ordering::Relaxed) + 1;
+                let status = match &outcome {
+                    Ok(_) => "ok".to_string(),
+                    Err(err) => format!("FAILED: {}", err),
+                };
+                eprintln!("[{}/{}] {} {}", finished, inputs.len(), input.display(), status);
+
+                results.lock().unwrap()[index] = Some(JobResult {
+                    input: input.clone(),
+                    outcome,
+                    elapsed,
+                });
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Prints one line per photo plus a total, failures at the bottom so they don't scroll away.
+pub fn print_summary(results: &[JobResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.input.display().to_string().len())
+        .max()
+        .unwrap_or(5)
+        .max(5);
+
+    eprintln!();
+    eprintln!("{:<width$}  {:<6}  {:>9}  {:>11}  {:>7}  OUTPUT", "INPUT", "STATUS", "DICE", "SIZE", "TIME");
+    for result in results {
+        if let Ok(outcome) = &result.outcome {
+            eprintln!(
+                "{:<width$}  {:<6}  {:>9}  {:>11}  {:>6.2}s  {}",
+                result.input.display().to_string(),
+                "ok",
+                format!("{}x{}", outcome.grid.0, outcome.grid.1),
+                format!("{}x{}", outcome.output_size.0, outcome.output_size.1),
+                result.elapsed.as_secs_f32(),
+                outcome.output.display(),
+            );
+        }
+    }
+    for result in results {
+        if let Err(err) = &result.outcome {
+            eprintln!(
+                "{:<width$}  {:<6}  {:>9}  {:>11}  {:>6.2}s  {}",
+                result.input.display().to_string(),
+                "FAILED",
+                "-",
+                "-",
+                result.elapsed.as_secs_f32(),
+                err,
+            );
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    let total: f32 = results.iter().map(|r| r.elapsed.as_secs_f32()).sum();
+    eprintln!();
+    eprintln!(
+        "{} rendered, {} failed, {:.2}s of work",
+        results.len() - failed,
+        failed,
+        total
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existing_files_arent_globs() {
+        let dir = std::env::temp_dir().join(format!("dice-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("IMG[1]?.png");
+        std::fs::write(&file, b"").unwrap();
+
+        assert!(!is_batch_input(file.to_str().unwrap()));
+        assert!(is_batch_input(dir.to_str().unwrap()));
+        assert!(is_batch_input(dir.join("*.png").to_str().unwrap()));
+        assert!(is_batch_input(dir.join("IMG[2].png").to_str().unwrap()));
+        assert!(!is_batch_input(dir.join("missing.png").to_str().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_fill_in_the_template() {
+        let input = Path::new("shoot/IMG_1.jpg");
+        assert_eq!(output_name("{stem}_dice.{ext}", input, 3, "png"), "IMG_1_dice.png");
+        assert_eq!(output_name("{index}-{name}.{ext}", input, 3, "webp"), "3-IMG_1.jpg.webp");
+    }
+
+    #[test]
+    fn colliding_names_are_rejected() {
+        let inputs = vec![PathBuf::from("a/IMG_1.jpg"), PathBuf::from("a/IMG_2.jpg"), PathBuf::from("b/IMG_1.png")];
+        let err = output_names("{stem}_dice.{ext}", &inputs, "png").unwrap_err();
+        assert!(matches!(err, DiceError::InvalidOptions(_)));
+        assert!(err.to_string().contains("a/IMG_1.jpg"));
+        assert!(err.to_string().contains("b/IMG_1.png"));
+
+        let names = output_names("{stem}_{index}.{ext}", &inputs, "png").unwrap();
+        assert_eq!(names, ["IMG_1_1.png", "IMG_2_2.png", "IMG_1_3.png"]);
+    }
+}
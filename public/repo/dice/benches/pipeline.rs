@@ -0,0 +1,105 @@
+//! The hot paths on a 4K (3840x2160) photo, the way they used to be done next to the way they're done now.
+//! `cargo bench --bench pipeline`, add `-- analysis` or `-- render` to run one group.
+//!
+//! The photo and faces are made up on the spot so the numbers don't depend on any files.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dice::dicelib::{block_averages, build_grid, map_intensity_to_dice_side, Dice, DiceGrid, IntensityPreset};
+use dice::{DiceRenderer, Hooks, RenderOptions};
+use image::{imageops, GrayImage, Luma, Rgba, RgbaImage};
+
+const WIDTH: u32 = 3840;
+const HEIGHT: u32 = 2160;
+
+/// Gradients and rings, so every face gets used.
+fn photo() -> GrayImage {
+    GrayImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let ring = ((x as f32 - 1920.0).hypot(y as f32 - 1080.0) / 40.0).sin() * 60.0;
+        Luma([((x * 255 / WIDTH) as f32 * 0.7 + ring + 60.0).clamp(0.0, 255.0) as u8])
+    })
+}
+
+/// Six opaque faces, lighter from One to Six, with a dark pip in the middle.
+fn face_pngs(size: u32) -> Vec<Vec<u8>> {
+    (1..=6u32)
+        .map(|n| {
+            let shade = (n * 40) as u8;
+            let face = RgbaImage::from_fn(size, size, |x, y| {
+                let centre = (x as i32 - size as i32 / 2).abs() + (y as i32 - size as i32 / 2).abs();
+                if centre < size as i32 / 6 { Rgba([20, 20, 20, 255]) } else { Rgba([shade, shade, shade, 255]) }
+            });
+            let mut bytes = Vec::new();
+            face.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+            bytes
+        })
+        .collect()
+}
+
+/// Block averages the old way: every block copied out with crop_imm(...).to_image().
+fn averages_by_copying(input: &GrayImage, dw: u32, dh: u32, preset: &IntensityPreset) -> DiceGrid {
+    let (num_x, num_y) = (input.width() / dw, input.height() / dh);
+    let mut cells = Vec::with_capacity((num_x * num_y) as usize);
+    for gy in 0..num_y {
+        for gx in 0..num_x {
+            let block = imageops::crop_imm(input, gx * dw, gy * dh, dw, dh).to_image();
+            let total: u64 = block.pixels().map(|p| p[0] as u64).sum();
+            cells.push(map_intensity_to_dice_side((total / (dw * dh) as u64) as u8, preset));
+        }
+    }
+    DiceGrid { width: num_x, height: num_y, cells }
+}
+
+/// Rendering the old way: find the face, convert it to RGBA and alpha-blend it, for every die.
+fn render_by_overlay(grid: &DiceGrid, dice: &[Dice]) -> RgbaImage {
+    let (dw, dh) = (dice[0].image.width(), dice[0].image.height());
+    let mut out = RgbaImage::new(grid.width * dw, grid.height * dh);
+    for gy in 0..grid.height {
+        for gx in 0..grid.width {
+            let side = grid.get(gx, gy);
+            let die = dice.iter().find(|d| d.side == side).unwrap();
+            imageops::overlay(&mut out, &die.image.to_rgba8(), (gx * dw) as i64, (gy * dh) as i64);
+        }
+    }
+    out
+}
+
+fn analysis(c: &mut Criterion) {
+    let photo = photo();
+    let preset = IntensityPreset::Default;
+    let mut group = c.benchmark_group("analysis");
+    group.sample_size(10);
+    for size in [8, 16, 32] {
+        group.bench_with_input(BenchmarkId::new("crop_copy", size), &size, |b, &size| {
+            b.iter(|| averages_by_copying(&photo, size, size, &preset))
+        });
+        group.bench_with_input(BenchmarkId::new("integral", size), &size, |b, &size| {
+            b.iter(|| build_grid(&photo, size, size, &preset))
+        });
+    }
+    group.bench_function("integral_only_averages/16", |b| b.iter(|| block_averages(&photo, 16, 16)));
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    let photo = photo();
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    // Dice size 16 makes a 240x135 grid, drawn back out at 16px that's 4K again
+    for size in [8, 16, 32] {
+        let options = RenderOptions { dice_size: size, ..RenderOptions::default() };
+        let renderer = DiceRenderer::from_face_bytes(options, &face_pngs(size)).unwrap();
+        let grid = renderer.analyze(&photo, &mut Hooks::none()).unwrap();
+        assert_eq!(render_by_overlay(&grid, renderer.dice()), renderer.render(&grid, &mut Hooks::none()).unwrap());
+
+        group.bench_with_input(BenchmarkId::new("overlay", size), &grid, |b, grid| {
+            b.iter(|| render_by_overlay(grid, renderer.dice()))
+        });
+        group.bench_with_input(BenchmarkId::new("blit", size), &grid, |b, grid| {
+            b.iter(|| renderer.render(grid, &mut Hooks::none()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, analysis, render);
+criterion_main!(benches);
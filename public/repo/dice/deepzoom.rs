@@ -0,0 +1,245 @@
+use std::path::{Path, PathBuf};
+
+use image::{imageops, RgbaImage};
+
+use crate::dicelib::{mosaic_size, Dice, DiceGrid};
+use crate::error::DiceError;
+
+/// Edge length of every tile in the pyramid.
+pub const TILE_SIZE: u32 = 256;
+
+/// Writes the grid as a DeepZoom pyramid: `<name>.dzi` plus `<name>_files/<level>/<col>_<row>.png`.
+///
+/// The full-size mosaic is never held in memory. The top level is drawn tile by tile straight
+/// from the grid, and every level below is built by halving 2x2 tiles of the level above.
+/// Every die is drawn alike and edge to edge: no variation, gap or debug overlay.
+/// Returns the path of the `.dzi` descriptor.
+pub fn write_deep_zoom(
+    grid: &DiceGrid,
+    dice: &[Dice],
+    dice_size: (u32, u32),
+    out_dir: &Path,
+    name: &str,
+) -> Result<PathBuf, DiceError> {
+    let (dw, dh) = dice_size;
+    let (width, height) = mosaic_size(grid, dice_size, 0)?;
+    if width == 0 || height == 0 {
+        return Err(DiceError::InvalidOptions("Nothing to tile, the dice grid is empty".to_string()));
+    }
+
+    let files_dir = out_dir.join(format!("{}_files", name));
+    let max_level = max_level(width, height);
+
+    // Convert the faces once instead of per tile
+    let faces: Vec<_> = dice.iter().map(|d| (d.side, d.image.to_rgba8())).collect();
+
+    // Top level, rendered from the grid
+    let level_dir = files_dir.join(max_level.to_string());
+    std::fs::create_dir_all(&level_dir).map_err(|e| DiceError::io(&level_dir, e))?;
+    for row in 0..tiles_for(height) {
+        for col in 0..tiles_for(width) {
+            let x0 = col * TILE_SIZE;
+            let y0 = row * TILE_SIZE;
+            let mut tile = RgbaImage::new(TILE_SIZE.min(width - x0), TILE_SIZE.min(height - y0));
+
+            // Only the dice that overlap this tile
+            for gy in y0 / dh..((y0 + tile.height()).div_ceil(dh)).min(grid.height) {
+                for gx in x0 / dw..((x0 + tile.width()).div_ceil(dw)).min(grid.width) {
+                    let side = grid.get(gx, gy);
+                    if let Some((_, face)) = faces.iter().find(|(s, _)| *s == side) {
+                        let px = (gx * dw) as i64 - x0 as i64;
+                        let py = (gy * dh) as i64 - y0 as i64;
+                        imageops::overlay(&mut tile, face, px, py);
+                    }
+                }
+            }
+
+            save_tile(&tile, &level_dir, col, row)?;
+        }
+    }
+
+    // Every lower level halves the one above it
+    let (mut level_w, mut level_h) = (width, height);
+    for level in (0..max_level).rev() {
+        let above_dir = files_dir.join((level + 1).to_string());
+        let level_dir = files_dir.join(level.to_string());
+        std::fs::create_dir_all(&level_dir).map_err(|e| DiceError::io(&level_dir, e))?;
+
+        let (above_w, above_h) = (level_w, level_h);
+        level_w = level_w.div_ceil(2);
+        level_h = level_h.div_ceil(2);
+
+        for row in 0..tiles_for(level_h) {
+            for col in 0..tiles_for(level_w) {
+                let x0 = col * TILE_SIZE * 2;
+                let y0 = row * TILE_SIZE * 2;
+                let mut region = RgbaImage::new(
+                    (TILE_SIZE * 2).min(above_w - x0),
+                    (TILE_SIZE * 2).min(above_h - y0),
+                );
+
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let path = tile_path(&above_dir, col * 2 + dx, row * 2 + dy);
+                    if !path.exists() {
+                        continue; // Right or bottom edge
+                    }
+                    let child = image::open(&path).map_err(|e| DiceError::image(&path, e))?.to_rgba8();
+                    imageops::overlay(
+                        &mut region,
+                        &child,
+                        (dx * TILE_SIZE) as i64,
+                        (dy * TILE_SIZE) as i64,
+                    );
+                }
+
+                let tile = imageops::resize(
+                    &region,
+                    region.width().div_ceil(2),
+                    region.height().div_ceil(2),
+                    imageops::FilterType::Triangle,
+                );
+                save_tile(&tile, &level_dir, col, row)?;
+            }
+        }
+    }
+
+    let descriptor = out_dir.join(format!("{}.dzi", name));
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"png\" Overlap=\"0\" TileSize=\"{}\">\n  \
+         <Size Width=\"{}\" Height=\"{}\"/>\n\
+         </Image>\n",
+        TILE_SIZE, width, height
+    );
+    std::fs::write(&descriptor, xml).map_err(|e| DiceError::io(&descriptor, e))?;
+
+    Ok(descriptor)
+}
+
+/// Level where the image is at full size. Level 0 is always 1x1.
+fn max_level(width: u32, height: u32) -> u32 {
+    let longest = width.max(height);
+    u32::BITS - (longest - 1).leading_zeros()
+}
+
+fn tiles_for(pixels: u32) -> u32 {
+    pixels.div_ceil(TILE_SIZE)
+}
+
+fn tile_path(level_dir: &Path, col: u32, row: u32) -> PathBuf {
+    level_dir.join(format!("{}_{}.png", col, row))
+}
+
+fn save_tile(tile: &RgbaImage, level_dir: &Path, col: u32, row: u32) -> Result<(), DiceError> {
+    let path = tile_path(level_dir, col, row);
+    tile.save(&path).map_err(|e| match e {
+        image::ImageError::IoError(source) => DiceError::io(&path, source),
+        e => DiceError::Encode(e.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicelib::DiceSides;
+    use image::{DynamicImage, Rgba};
+
+    /// Pyramid of a `columns` x `rows` grid of plain `dw` x `dh` dice, in a fresh temp dir.
+    fn pyramid(test: &str, columns: u32, rows: u32, (dw, dh): (u32, u32)) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dice-deepzoom-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let grid = DiceGrid { width: columns, height: rows, cells: vec![DiceSides::Three; (columns * rows) as usize] };
+        let face = DynamicImage::ImageRgba8(RgbaImage::from_pixel(dw, dh, Rgba([200, 10, 10, 255])));
+        let dice = vec![Dice { side: DiceSides::Three, image: face }];
+        write_deep_zoom(&grid, &dice, (dw, dh), &dir, "dz").unwrap();
+        dir
+    }
+
+    /// Sizes of the tiles of one level, indexed [row][col].
+    fn tile_sizes(dir: &Path, level: u32) -> Vec<Vec<(u32, u32)>> {
+        let level_dir = dir.join("dz_files").join(level.to_string());
+        let mut rows = Vec::new();
+        for row in 0.. {
+            let mut sizes = Vec::new();
+            for col in 0.. {
+                match image::image_dimensions(tile_path(&level_dir, col, row)) {
+                    Ok(size) => sizes.push(size),
+                    Err(_) => break,
+                }
+            }
+            if sizes.is_empty() {
+                break;
+            }
+            rows.push(sizes);
+        }
+        rows
+    }
+
+    /// Every level has the tiles to cover it exactly: full tiles, a partial one at the right and
+    /// bottom edges, nothing overlapping and nothing past the edge.
+    fn assert_levels(dir: &Path, width: u32, height: u32) {
+        let top = max_level(width, height);
+        assert!(!dir.join("dz_files").join((top + 1).to_string()).exists());
+        for level in 0..=top {
+            let (w, h) = (width.div_ceil(1 << (top - level)), height.div_ceil(1 << (top - level)));
+            let tiles = tile_sizes(dir, level);
+            assert_eq!(tiles.len() as u32, tiles_for(h), "rows at level {}", level);
+            for row in &tiles {
+                assert_eq!(row.len() as u32, tiles_for(w), "columns at level {}", level);
+                assert_eq!(row.iter().map(|t| t.0).sum::<u32>(), w, "width of level {}", level);
+                assert!(row[..row.len() - 1].iter().all(|t| t.0 == TILE_SIZE));
+            }
+            assert_eq!(tiles.iter().map(|r| r[0].1).sum::<u32>(), h, "height of level {}", level);
+        }
+        let level_0 = tile_sizes(dir, 0);
+        assert_eq!(level_0, vec![vec![(1, 1)]]);
+    }
+
+    #[test]
+    fn levels_go_down_to_one_pixel() {
+        assert_eq!(max_level(1, 1), 0);
+        assert_eq!(max_level(2, 1), 1);
+        assert_eq!(max_level(256, 256), 8);
+        assert_eq!(max_level(257, 3), 9);
+        assert_eq!(max_level(600, 1000), 10);
+    }
+
+    #[test]
+    fn tiles_round_up() {
+        assert_eq!(tiles_for(1), 1);
+        assert_eq!(tiles_for(256), 1);
+        assert_eq!(tiles_for(257), 2);
+        assert_eq!(tiles_for(600), 3);
+    }
+
+    #[test]
+    fn single_pixel() {
+        let dir = pyramid("pixel", 1, 1, (1, 1));
+        assert_levels(&dir, 1, 1);
+        let dzi = std::fs::read_to_string(dir.join("dz.dzi")).unwrap();
+        assert!(dzi.contains("<Size Width=\"1\" Height=\"1\"/>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_tiles_at_the_edges() {
+        // 600x300: not a power of two, with a 88 wide last column and a 44 high last row on top
+        let dir = pyramid("edges", 6, 3, (100, 100));
+        assert_eq!(tile_sizes(&dir, 10), vec![
+            vec![(256, 256), (256, 256), (88, 256)],
+            vec![(256, 44), (256, 44), (88, 44)],
+        ]);
+        assert_eq!(tile_sizes(&dir, 9), vec![vec![(256, 150), (44, 150)]]);
+        assert_levels(&dir, 600, 300);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_or_overflowing_grids_are_errors() {
+        let dir = std::env::temp_dir();
+        let empty = DiceGrid { width: 0, height: 0, cells: Vec::new() };
+        assert!(matches!(write_deep_zoom(&empty, &[], (10, 10), &dir, "dz"), Err(DiceError::InvalidOptions(_))));
+        let wide = DiceGrid { width: 70_000, height: 1, cells: vec![DiceSides::One; 70_000] };
+        assert!(matches!(write_deep_zoom(&wide, &[], (70_000, 1), &dir, "dz"), Err(DiceError::InvalidOptions(_))));
+    }
+}
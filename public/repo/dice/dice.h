@@ -0,0 +1,148 @@
+/* Dice mosaics from C and C++, generated from ffi.rs by cbindgen. Link against the library built with `--features ffi`. */
+
+#ifndef DICE_H
+#define DICE_H
+
+#include <stdint.h>
+#include <stddef.h>
+
+// What a call came to. Failures have the numbers the CLI exits with.
+typedef enum {
+  DICE_STATUS_OK = 0,
+  // Settings that make no sense, also null pointers and text that isn't UTF-8.
+  DICE_STATUS_INVALID_OPTIONS = 2,
+  DICE_STATUS_IO = 3,
+  // A photo or the settings couldn't be understood.
+  DICE_STATUS_DECODE = 4,
+  DICE_STATUS_INVALID_FACE_SET = 5,
+  DICE_STATUS_ENCODE = 6,
+  // A bug in the library, the message says where.
+  DICE_STATUS_PANIC = 101,
+  // The job was cancelled with `dice_job_cancel`.
+  DICE_STATUS_CANCELLED = 130,
+} DiceStatus;
+
+// What a job is busy with, for the progress callback.
+typedef enum {
+  DICE_STAGE_ANALYSING,
+  DICE_STAGE_RENDERING,
+  DICE_STAGE_ENCODING,
+} DiceStage;
+
+// Tiles to draw with, darkest first. Can make any number of jobs and is freed with `dice_face_set_free`.
+typedef struct DiceFaceSet DiceFaceSet;
+
+// A renderer for one set of settings, for any number of photos until it's cancelled.
+// Freed with `dice_job_free`.
+typedef struct DiceJob DiceJob;
+
+// Called on the rendering thread between rows of dice: `done` out of `total` rows of the stage.
+typedef void (*DiceProgressCallback)(DiceStage stage, uint32_t done, uint32_t total, void *user_data);
+
+// Bytes owned by the library, freed with `dice_buffer_free`.
+typedef struct {
+  uint8_t *data;
+  size_t len;
+} DiceBuffer;
+
+// RGBA pixels, `width * 4` bytes a row, owned by the library and freed with `dice_image_free`.
+typedef struct {
+  uint8_t *pixels;
+  uint32_t width;
+  uint32_t height;
+} DiceImage;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last call on this thread that failed, NULL if none has.
+// It stays valid until the next call on this thread fails.
+const char *dice_last_error(void);
+
+// Version of the library, e.g. "0.1.0".
+const char *dice_version(void);
+
+// Opens a dice directory, picked the way the CLI does (faces.toml, otherwise natural order).
+//
+// # Safety
+// `dir` is a NUL-terminated UTF-8 path and `out` points at writable space for the handle.
+DiceStatus dice_face_set_open(const char *dir, DiceFaceSet **out);
+
+// A face set from `count` encoded images (PNG, JPEG, ...) in memory, One first.
+// The bytes are decoded straight away and needn't outlive the call.
+//
+// # Safety
+// `images` and `lengths` point at `count` entries, `images[i]` at `lengths[i]` readable bytes,
+// and `out` at writable space for the handle.
+DiceStatus dice_face_set_from_images(const uint8_t *const *images,
+                                     const size_t *lengths,
+                                     size_t count,
+                                     DiceFaceSet **out);
+
+// Dice drawn at whatever size they're needed, no photos. Never fails.
+DiceFaceSet *dice_face_set_procedural(void);
+
+// # Safety
+// `faces` is NULL or a handle from a `dice_face_set_` call, not freed yet.
+void dice_face_set_free(DiceFaceSet *faces);
+
+// A job rendering with `faces` and `settings`, a profile as JSON or TOML text of any version
+// (`dice schema` prints its JSON Schema). NULL settings take the defaults; NULL faces use the
+// profile's dice-dir. The face set can be freed once the job is made.
+//
+// # Safety
+// `faces` is NULL or a live face set, `settings` NULL or a NUL-terminated string, and `out`
+// points at writable space for the handle.
+DiceStatus dice_job_new(const DiceFaceSet *faces, const char *settings, DiceJob **out);
+
+// Calls `callback` with `user_data` between rows of dice while the job renders, NULL to stop.
+//
+// # Safety
+// `job` is a live job that isn't rendering.
+void dice_job_set_progress(DiceJob *job, DiceProgressCallback callback, void *user_data);
+
+// Stops the job: a render in progress returns `DICE_STATUS_CANCELLED` after its current rows,
+// and so does every render after. The one call that's safe from another thread while the job renders.
+//
+// # Safety
+// `job` is a live job.
+void dice_job_cancel(const DiceJob *job);
+
+// Renders an encoded photo (any format the image crate reads) into `out`, encoded in the settings'
+// format (PNG unless changed) with the settings embedded.
+//
+// # Safety
+// `job` is a live job, `photo` points at `len` readable bytes and `out` at a writable `DiceBuffer`.
+DiceStatus dice_job_render(const DiceJob *job, const uint8_t *photo, size_t len, DiceBuffer *out);
+
+// Renders an encoded photo into RGBA pixels in `out`, e.g. to put straight on screen.
+//
+// # Safety
+// `job` is a live job, `photo` points at `len` readable bytes and `out` at a writable `DiceImage`.
+DiceStatus dice_job_render_rgba(const DiceJob *job,
+                                const uint8_t *photo,
+                                size_t len,
+                                DiceImage *out);
+
+// # Safety
+// `job` is NULL or a job from `dice_job_new` that isn't rendering, not freed yet.
+void dice_job_free(DiceJob *job);
+
+// Frees the bytes and empties the buffer, freeing an empty buffer does nothing.
+//
+// # Safety
+// `buffer` is NULL or a buffer filled in by the library.
+void dice_buffer_free(DiceBuffer *buffer);
+
+// Frees the pixels and empties the image, freeing an empty image does nothing.
+//
+// # Safety
+// `image` is NULL or an image filled in by the library.
+void dice_image_free(DiceImage *image);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DICE_H */
@@ -0,0 +1,95 @@
+# Types for the Python module built from python.rs (`maturin build --no-default-features --features python`).
+
+import os
+from typing import Any, Callable, Mapping, Optional, Sequence, Union
+
+import numpy as np
+import numpy.typing as npt
+
+# (rows, columns) of faces 1-6, as `Renderer.analyze` and `read_grid` give them
+Grid = npt.NDArray[np.uint8]
+# Encoded photo bytes (any format the image crate reads) or a path to one
+Photo = Union[bytes, str, os.PathLike[str]]
+# Called between rows of dice as (stage, done, total), stage "analysing", "rendering" or "encoding".
+# Raising stops the run and the exception comes out of the call.
+OnProgress = Callable[[str, int, int], None]
+
+class DiceError(Exception): ...
+class IoError(DiceError): ...
+class DecodeError(DiceError): ...
+class InvalidFaceSetError(DiceError): ...
+class InvalidOptionsError(DiceError): ...
+class EncodeError(DiceError): ...
+class CancelledError(DiceError): ...
+
+class FaceSet:
+    """Tiles to draw with, darkest first (for dice, face One to Six)."""
+
+    @staticmethod
+    def open(path: Union[str, os.PathLike[str]]) -> FaceSet:
+        """A dice directory, picked the way the CLI does (faces.toml, otherwise natural order)."""
+    @staticmethod
+    def from_images(images: Sequence[bytes]) -> FaceSet:
+        """Encoded images (PNG, JPEG, ...), One first."""
+    @staticmethod
+    def procedural() -> FaceSet:
+        """Dice drawn at whatever size they're needed, no photos."""
+    @staticmethod
+    def glyphs(chars: str, font: Optional[bytes] = None) -> FaceSet:
+        """Characters of a font (the bundled one unless `font` is given), sorted darkest first."""
+    def __len__(self) -> int: ...
+    @property
+    def tones(self) -> list[int]:
+        """Average brightness of each tile, 0-255."""
+    @property
+    def report(self) -> str:
+        """Which file became which face, empty unless opened from a directory."""
+    @property
+    def fingerprint(self) -> str: ...
+
+class Renderer:
+    """A renderer for one set of settings, for any number of photos or grids.
+
+    `settings` is profile text (JSON or TOML), a `pathlib.Path` to a profile or a dict with the
+    profile keys (`settings_schema()`), and keyword arguments win over it, with `_` for `-`:
+    `Renderer(faces, dice_size=16, preset="bright")`. Without faces it uses the profile's
+    dice-dir, and without either it can analyse but not render.
+    """
+
+    def __init__(
+        self,
+        faces: Optional[FaceSet] = None,
+        settings: Union[str, os.PathLike[str], Mapping[str, Any], None] = None,
+        **overrides: Any,
+    ) -> None: ...
+    def analyze(self, photo: Photo, on_progress: Optional[OnProgress] = None) -> Grid:
+        """Faces 1-6 for a photo, one per die."""
+    def render(self, source: Union[Photo, Grid], on_progress: Optional[OnProgress] = None) -> bytes:
+        """The mosaic encoded in the settings' format (PNG unless changed), settings embedded."""
+    def render_image(self, source: Union[Photo, Grid], on_progress: Optional[OnProgress] = None) -> npt.NDArray[np.uint8]:
+        """The mosaic as a (height, width, 4) RGBA array, ready for `PIL.Image.fromarray`."""
+    @property
+    def settings(self) -> str:
+        """Every setting used, as a JSON profile the CLI can load with --config."""
+    @property
+    def mime_type(self) -> str: ...
+    @property
+    def extension(self) -> str: ...
+
+def read_grid(text: str) -> Grid:
+    """A grid file (DICEGRID text, as `dice plan` writes it)."""
+
+def grid_text(grid: Grid) -> str:
+    """The grid as DICEGRID text, for `dice render --grid` or `read_grid`."""
+
+def face_counts(grid: Grid) -> list[int]:
+    """How many of each face the grid takes, One first."""
+
+def bom_csv(grid: Grid) -> str:
+    """Bill of materials as CSV, one line per face plus the total."""
+
+def usage_table(grid: Grid) -> str:
+    """The face usage table `dice plan` prints."""
+
+def settings_schema() -> str:
+    """JSON Schema of the settings `Renderer` takes."""
@@ -3,6 +3,11 @@ use image::{imageops, open, DynamicImage, GrayImage, Rgba, RgbaImage};
 use imageproc::drawing::{draw_filled_rect_mut, draw_text_mut};
 use imageproc::rect::Rect;
 
+use crate::error::DiceError;
+use crate::gridfile::{parse_grid, write_grid};
+use crate::parallel::map_rows;
+use crate::variation::{draw_picked, Variation};
+
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)] // Added derives for mapping
@@ -15,20 +20,52 @@ pub enum DiceSides {
     Six,
 }
 
+impl DiceSides {
+    /// Pips on the face, 1-6.
+    pub fn number(self) -> u8 {
+        match self {
+            DiceSides::One => 1,
+            DiceSides::Two => 2,
+            DiceSides::Three => 3,
+            DiceSides::Four => 4,
+            DiceSides::Five => 5,
+            DiceSides::Six => 6,
+        }
+    }
+
+    pub fn from_number(number: u8) -> Option<DiceSides> {
+        match number {
+            1 => Some(DiceSides::One),
+            2 => Some(DiceSides::Two),
+            3 => Some(DiceSides::Three),
+            4 => Some(DiceSides::Four),
+            5 => Some(DiceSides::Five),
+            6 => Some(DiceSides::Six),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)] // Added Clone
 pub struct Dice {
     pub side: DiceSides, // Uses the simplified enum
     pub image: DynamicImage,
 }
 
+/// DejaVu Sans Bold, for the overlay text and glyph tiles.
+pub const BUNDLED_FONT: &[u8] = include_bytes!("DejaVuSans-Bold.ttf");
+
+/// Height of the black bar add_reference_text draws across the top, the text stays inside it.
+pub const REFERENCE_BAR_HEIGHT: u32 = 28;
+
 pub fn add_reference_text(
     image: &mut RgbaImage,
     dice_size: (u32, u32),
     total_dice: u32,
     full_image_size: (u32, u32),
-) {
-    let font_data = include_bytes!("../DejaVuSans-Bold.ttf");
-    let font = FontVec::try_from_vec(font_data.to_vec()).expect("Failed to load font");
+) -> Result<(), DiceError> {
+    let font = FontVec::try_from_vec(BUNDLED_FONT.to_vec())
+        .map_err(|e| DiceError::Decode(format!("Couldn't load the debug overlay font: {}", e)))?;
     let scale = PxScale::from(20.0); // Font size
 
     let text = format!(
@@ -37,97 +74,487 @@ pub fn add_reference_text(
     );
 
     // Calculate text dimensions
-    let text_width = text.len() as u32 * 12; // Approximate width per character
-    let text_height = 24; // Approximate height of the text
+    let text_width = (text.len() as u32).saturating_mul(12); // Approximate width per character
+    let text_height = REFERENCE_BAR_HEIGHT;
 
-    // Draw black background rectangle
-    let rect = Rect::at(0, 0).of_size(text_width, text_height);
-    draw_filled_rect_mut(image, rect, Rgba([0, 0, 0, 255])); // Black background
+    // Black bar across the top (at least as wide as the text), opaque so it reads the same in every format
+    let rect = Rect::at(0, 0).of_size(text_width.max(full_image_size.0), text_height);
+    draw_filled_rect_mut(image, rect, Rgba([0, 0, 0, 255]));
 
     // Draw the text
     draw_text_mut(
         image,
         Rgba([255, 255, 255, 255]), // White text
-        5,                          // X offset
-        5,                          // Y offset
+        6,                          // X offset
+        4,                          // Y offset
         scale,
         &font,
         &text,
     );
+    Ok(())
 }
 
 
 /// Loads and returns a GrayImage
-pub fn load_image(input_path: &str) -> GrayImage {
-    // Path is currently hardcoded inside, consider passing _input_path through
-
-   // let img = ImageReader::open("images/flag.jpeg").unwrap()
-   //     .decode()
-   //     .expect("Failed to decode image")
-   //     .into_luma8();
-
-   let img = open(input_path) // Use _input_path here if needed
-       .expect("Failed to load input image")
-       .into_luma8();
-
-   // // conditional resize here later
-//    let dynamic_image = DynamicImage::ImageLuma8(img);
-//    let resized = dynamic_image.resize(2048,2048, image::imageops::FilterType::Lanczos3).into_luma8();
-//    return resized;
-
-   img // Return original grayscale image if no resize
+pub fn load_image(input_path: &str) -> Result<GrayImage, DiceError> {
+    let img = open(input_path).map_err(|e| DiceError::image(input_path, e))?.into_luma8();
+    Ok(img)
 }
 
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IntensityPreset {
     Default,
     HighContrast,
     LowContrast,
     Bright,
-    Dark
+    Dark,
+    Custom([u8; 5]), // highest tone for faces One to Five, Six gets the rest
+}
+
+/// The named presets, in the order they're offered.
+pub const PRESETS: [IntensityPreset; 5] = [
+    IntensityPreset::Default,
+    IntensityPreset::HighContrast,
+    IntensityPreset::LowContrast,
+    IntensityPreset::Bright,
+    IntensityPreset::Dark,
+];
+
+impl IntensityPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntensityPreset::Default => "Default",
+            IntensityPreset::HighContrast => "HighContrast",
+            IntensityPreset::LowContrast => "LowContrast",
+            IntensityPreset::Bright => "Bright",
+            IntensityPreset::Dark => "Dark",
+            IntensityPreset::Custom(_) => "Custom",
+        }
+    }
+
+    /// Name as used on the command line and in profiles.
+    pub fn cli_name(&self) -> &'static str {
+        match self {
+            IntensityPreset::Default => "default",
+            IntensityPreset::HighContrast => "high-contrast",
+            IntensityPreset::LowContrast => "low-contrast",
+            IntensityPreset::Bright => "bright",
+            IntensityPreset::Dark => "dark",
+            IntensityPreset::Custom(_) => "custom",
+        }
+    }
+
+    /// Accepts either spelling, "HighContrast" or "high-contrast".
+    /// Custom isn't a name, it needs its thresholds.
+    pub fn from_name(name: &str) -> Option<IntensityPreset> {
+        match name.replace('-', "").to_ascii_lowercase().as_str() {
+            "default" => Some(IntensityPreset::Default),
+            "highcontrast" => Some(IntensityPreset::HighContrast),
+            "lowcontrast" => Some(IntensityPreset::LowContrast),
+            "bright" => Some(IntensityPreset::Bright),
+            "dark" => Some(IntensityPreset::Dark),
+            _ => None,
+        }
+    }
+
+    /// Highest average tone that still gets face One, Two, ... Five. Anything brighter is a Six.
+    pub fn thresholds(&self) -> [u8; 5] {
+        match *self {
+            IntensityPreset::Default => [50, 100, 150, 200, 230],
+            IntensityPreset::HighContrast => [42, 85, 128, 171, 214],
+            IntensityPreset::LowContrast => [60, 120, 180, 210, 240],
+            IntensityPreset::Bright => [30, 80, 130, 180, 220],
+            IntensityPreset::Dark => [70, 120, 160, 200, 240],
+            IntensityPreset::Custom(thresholds) => thresholds,
+        }
+    }
+}
+
+/// Parses custom thresholds, e.g. `40,90,140,190,225`. They have to go up.
+pub fn parse_thresholds(value: &str) -> Result<[u8; 5], String> {
+    let values: Vec<u8> = value
+        .split(',')
+        .map(|v| v.trim().parse::<u8>().map_err(|_| format!("{} isn't a tone from 0 to 255", v.trim())))
+        .collect::<Result<_, _>>()?;
+    let thresholds: [u8; 5] = values
+        .try_into()
+        .map_err(|v: Vec<u8>| format!("expected 5 thresholds (faces One to Five), got {}", v.len()))?;
+    if thresholds.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(format!("thresholds have to go up, got {}", value));
+    }
+    Ok(thresholds)
 }
 
 pub fn map_intensity_to_dice_side(avg_intensity: u8, preset: &IntensityPreset) -> DiceSides {
-    match preset {
-        IntensityPreset::Default => match avg_intensity {
-            0..=50 => DiceSides::One,
-            51..=100 => DiceSides::Two,
-            101..=150 => DiceSides::Three,
-            151..=200 => DiceSides::Four,
-            201..=230 => DiceSides::Five,
-            231..=255 => DiceSides::Six,
-        },
-        IntensityPreset::HighContrast => match avg_intensity {
-            0..=42 => DiceSides::One,
-            43..=85 => DiceSides::Two,
-            86..=128 => DiceSides::Three,
-            129..=171 => DiceSides::Four,
-            172..=214 => DiceSides::Five,
-            215..=255 => DiceSides::Six,
-        },
-        IntensityPreset::LowContrast => match avg_intensity {
-            0..=60 => DiceSides::One,
-            61..=120 => DiceSides::Two,
-            121..=180 => DiceSides::Three,
-            181..=210 => DiceSides::Four,
-            211..=240 => DiceSides::Five,
-            241..=255 => DiceSides::Six,
-        },
-        IntensityPreset::Bright => match avg_intensity {
-            0..=30 => DiceSides::One,
-            31..=80 => DiceSides::Two,
-            81..=130 => DiceSides::Three,
-            131..=180 => DiceSides::Four,
-            181..=220 => DiceSides::Five,
-            221..=255 => DiceSides::Six,
-        },
-        IntensityPreset::Dark => match avg_intensity {
-            0..=70 => DiceSides::One,
-            71..=120 => DiceSides::Two,
-            121..=160 => DiceSides::Three,
-            161..=200 => DiceSides::Four,
-            201..=240 => DiceSides::Five,
-            241..=255 => DiceSides::Six,
+    let face = preset
+        .thresholds()
+        .iter()
+        .position(|&highest| avg_intensity <= highest)
+        .unwrap_or(5);
+    DiceSides::from_number(face as u8 + 1).unwrap()
+}
+
+/// Grid of dice faces picked for an image, stored row by row.
+#[derive(Debug, Clone)]
+pub struct DiceGrid {
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<DiceSides>,
+}
+
+impl DiceGrid {
+    pub fn get(&self, x: u32, y: u32) -> DiceSides {
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[DiceSides]> {
+        self.cells.chunks(self.width.max(1) as usize)
+    }
+
+    /// Saves the grid in the DICEGRID text format, see gridfile.rs.
+    pub fn to_text(&self) -> String {
+        let faces: Vec<u8> = self.cells.iter().map(|s| s.number()).collect();
+        write_grid(self.width, self.height, &faces)
+    }
+
+    pub fn from_text(text: &str) -> Result<DiceGrid, DiceError> {
+        let (width, height, faces) = parse_grid(text)?;
+        let cells = faces
+            .into_iter()
+            .map(|face| DiceSides::from_number(face).ok_or_else(|| DiceError::Decode(format!("{} isn't a die face", face))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DiceGrid { width, height, cells })
+    }
+}
+
+/// Summed-area table of a grayscale image, so the sum of any rectangle takes four lookups
+/// instead of a pass over its pixels.
+///
+/// Entries are u32 and wrap: the wrapped differences still give exact sums for any rectangle of
+/// up to MAX_LOOKUP_PIXELS pixels, bigger ones are added up a strip at a time. That keeps the table
+/// at four bytes per pixel.
+pub struct IntegralImage {
+    width: u32,
+    height: u32,
+    sums: Vec<u32>, // (width + 1) x (height + 1), the first row and column are zero
+}
+
+/// Largest rectangle whose sum is sure to fit in a u32.
+const MAX_LOOKUP_PIXELS: u64 = u32::MAX as u64 / 255;
+
+impl IntegralImage {
+    pub fn new(image: &GrayImage) -> IntegralImage {
+        let (width, height) = image.dimensions();
+        let stride = width as usize + 1;
+        let mut sums = vec![0u32; stride * (height as usize + 1)];
+        for (y, row) in image.as_raw().chunks_exact(width.max(1) as usize).enumerate() {
+            // Each row is the one above plus the running sum along this row
+            let (above, below) = sums.split_at_mut((y + 1) * stride);
+            let above = &above[y * stride + 1..];
+            let mut row_sum = 0u32;
+            for ((out, &up), &v) in below[1..stride].iter_mut().zip(above).zip(row) {
+                row_sum = row_sum.wrapping_add(v as u32);
+                *out = up.wrapping_add(row_sum);
+            }
+        }
+        IntegralImage { width, height, sums }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Sum of the w x h rectangle at x, y.
+    pub fn sum(&self, x: u32, y: u32, w: u32, h: u32) -> u64 {
+        let rows = (MAX_LOOKUP_PIXELS / w.max(1) as u64).max(1) as u32;
+        let mut total = 0u64;
+        let mut top = y;
+        while top < y + h {
+            let bottom = (top + rows).min(y + h);
+            total += self.lookup(x, top, x + w, bottom) as u64;
+            top = bottom;
         }
+        total
     }
-}
\ No newline at end of file
+
+    fn lookup(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> u32 {
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| self.sums[y as usize * stride + x as usize];
+        at(x1, y1).wrapping_sub(at(x0, y1)).wrapping_sub(at(x1, y0)).wrapping_add(at(x0, y0))
+    }
+
+    /// Average intensity of every dw x dh block, row by row, plus how many blocks across and down.
+    /// Rows of blocks are worked out in parallel, see parallel.rs.
+    pub fn block_averages(&self, dw: u32, dh: u32) -> (u32, u32, Vec<u8>) {
+        let num_x = self.width / dw;
+        let num_y = self.height / dh;
+        let num_pixels_in_block = dw as u64 * dh as u64;
+
+        let rows = map_rows(0..num_y, |grid_y| {
+            (0..num_x)
+                .map(|grid_x| {
+                    let total_intensity = self.sum(grid_x * dw, grid_y * dh, dw, dh);
+                    total_intensity.checked_div(num_pixels_in_block).unwrap_or(0) as u8
+                })
+                .collect::<Vec<u8>>()
+        });
+
+        (num_x, num_y, rows.concat())
+    }
+}
+
+/// Average intensity of every dw x dh block, row by row, plus how many blocks across and down.
+/// Keep an IntegralImage around instead when trying several dice sizes on the same photo.
+pub fn block_averages(input: &GrayImage, dw: u32, dh: u32) -> (u32, u32, Vec<u8>) {
+    IntegralImage::new(input).block_averages(dw, dh)
+}
+
+/// Average intensity of every dw x dh block, mapped to a dice side.
+pub fn build_grid(input: &GrayImage, dw: u32, dh: u32, preset: &IntensityPreset) -> DiceGrid {
+    let (num_x, num_y, averages) = block_averages(input, dw, dh);
+    DiceGrid {
+        width: num_x,
+        height: num_y,
+        cells: averages.iter().map(|&avg| map_intensity_to_dice_side(avg, preset)).collect(),
+    }
+}
+
+/// Inverts, crops to a square and fits the photo to the output canvas, ready for build_grid.
+pub fn prepare_photo(mut input: GrayImage, invert: bool, output_size: Option<(u32, u32)>) -> GrayImage {
+    if invert {
+        imageops::invert(&mut input);
+    }
+
+    // Crop the input image to a square. Dice grids love squares.
+    let (width, height) = input.dimensions();
+    let square_size = width.min(height);
+    input = imageops::crop_imm(&input, 0, 0, square_size, square_size).to_image();
+
+    // Resize the input image if needed
+    match output_size {
+        Some((output_width, output_height)) => fit_to_canvas(&input, output_width, output_height),
+        None => input,
+    }
+}
+
+/// Scales the input onto a blank output_width x output_height canvas, centered and undistorted.
+fn fit_to_canvas(input: &GrayImage, output_width: u32, output_height: u32) -> GrayImage {
+    // Create a new blank image with the desired dimensions
+    let mut resized_input = GrayImage::new(output_width, output_height);
+
+    // Calculate the aspect ratio of the original image
+    let (input_width, input_height) = input.dimensions();
+    let aspect_ratio = input_width as f32 / input_height as f32;
+
+    // Calculate the new dimensions for the original image while maintaining aspect ratio
+    let (new_width, new_height) = if output_width as f32 / output_height as f32 > aspect_ratio {
+        // Constrain by height
+        let new_width = (output_height as f32 * aspect_ratio).round() as u32;
+        (new_width, output_height)
+    } else {
+        // Constrain by width
+        let new_height = (output_width as f32 / aspect_ratio).round() as u32;
+        (output_width, new_height)
+    };
+
+    // Resize the original image to the new dimensions
+    let scaled_input = image::imageops::resize(
+        input,
+        new_width,
+        new_height,
+        imageops::FilterType::Lanczos3, // High-quality resizing filter
+    );
+
+    // Calculate the offsets to center the scaled image
+    let offset_x = ((output_width - new_width) / 2) as i64;
+    let offset_y = ((output_height - new_height) / 2) as i64;
+
+    // Overlay the scaled image onto the new blank image
+    imageops::overlay(&mut resized_input, &scaled_input, offset_x, offset_y);
+
+    resized_input
+}
+
+/// Width and height of the mosaic for a grid, with `gap` empty pixels between neighbours.
+/// An error if either side doesn't fit in a u32.
+pub fn mosaic_size(grid: &DiceGrid, (dw, dh): (u32, u32), gap: u32) -> Result<(u32, u32), DiceError> {
+    let side = |cells: u32, die: u32| {
+        die.checked_add(gap)
+            .and_then(|step| cells.checked_mul(step))
+            .map(|len| len.saturating_sub(gap))
+            .ok_or_else(|| {
+                DiceError::InvalidOptions(format!(
+                    "A {}x{} grid of {}x{} dice is too big for one image",
+                    grid.width, grid.height, dw, dh
+                ))
+            })
+    };
+    Ok((side(grid.width, dw)?, side(grid.height, dh)?))
+}
+
+/// Draws the dice for every cell of the grid, with `gap` empty pixels between neighbours.
+pub fn render_grid(grid: &DiceGrid, dice: &[Dice], gap: u32) -> Result<RgbaImage, DiceError> {
+    let tiles = Tiles::new(dice);
+    let (ow, oh) = mosaic_size(grid, tiles.size(), gap)?;
+    let mut oi = RgbaImage::new(ow, oh);
+    let band_bytes = (tiles.size().1 + gap) as usize * ow as usize * 4;
+
+    if !oi.is_empty() {
+        for (grid_y, band) in oi.chunks_mut(band_bytes).enumerate() {
+            tiles.draw_band(grid, gap, grid_y as u32, band);
+        }
+    }
+
+    Ok(oi)
+}
+
+/// The faces ready to be copied into a mosaic: converted to RGBA once, already blended over the
+/// transparent background, and indexed by side. Drawing a die is then a lookup and a memcpy per row
+/// of pixels, and comes out exactly as overlaying the face onto the empty mosaic would.
+pub struct Tiles {
+    width: u32,
+    height: u32,
+    faces: [Vec<RgbaImage>; 6], // One first, empty for a side without a face (left empty)
+    variation: Option<Variation>,
+}
+
+impl Tiles {
+    /// Tiles the size of the first face, one per side: the first die given for it.
+    pub fn new(dice: &[Dice]) -> Tiles {
+        let mut tiles = Tiles::varied(dice, None);
+        for face in &mut tiles.faces {
+            face.truncate(1);
+        }
+        tiles
+    }
+
+    /// Every die given for a side becomes one of its tiles (several photos of the same face), each
+    /// turned all four ways too if the face allows it. Dice then get one of them picked by `variation`.
+    pub fn varied(dice: &[Dice], variation: Option<Variation>) -> Tiles {
+        let (width, height) = dice.first().map_or((0, 0), |d| (d.image.width(), d.image.height()));
+        let mut faces: [Vec<RgbaImage>; 6] = Default::default();
+        for d in dice {
+            let mut tile = RgbaImage::new(width, height);
+            imageops::overlay(&mut tile, &d.image.to_rgba8(), 0, 0);
+            // Quarter turns only keep the size of square faces
+            let turned = if variation.is_some() && Variation::turns(d.side) && width == height {
+                vec![imageops::rotate90(&tile), imageops::rotate180(&tile), imageops::rotate270(&tile)]
+            } else {
+                Vec::new()
+            };
+            let face = &mut faces[d.side.number() as usize - 1];
+            face.push(tile);
+            face.extend(turned);
+        }
+        Tiles { width, height, faces, variation }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Draws row `grid_y` of the grid into `band`: raw RGBA rows as wide as the whole mosaic,
+    /// starting at the top of that row of dice. Gaps (and anything past the end of `band`) are left alone.
+    pub fn draw_band(&self, grid: &DiceGrid, gap: u32, grid_y: u32, band: &mut [u8]) {
+        // Callers have made the image already, so the size fits
+        let Ok((ow, _)) = mosaic_size(grid, self.size(), gap) else { return };
+        let row_bytes = ow as usize * 4;
+        let tile_row = self.width as usize * 4;
+        let rows = (self.height as usize).min(band.len() / row_bytes.max(1));
+
+        for (grid_x, side) in grid.cells[(grid_y * grid.width) as usize..][..grid.width as usize].iter().enumerate() {
+            let tiles = &self.faces[side.number() as usize - 1];
+            if tiles.is_empty() {
+                continue;
+            }
+            let x = grid_x * (self.width + gap) as usize * 4;
+            let tile = match &self.variation {
+                Some(variation) => {
+                    let pick = variation.pick(grid_x as u32, grid_y, tiles.len());
+                    if !pick.is_plain() {
+                        draw_picked(&tiles[pick.tile], &pick, band, row_bytes, x, rows);
+                        continue;
+                    }
+                    &tiles[pick.tile]
+                }
+                None => &tiles[0],
+            };
+            for (r, src) in tile.as_raw().chunks_exact(tile_row).take(rows).enumerate() {
+                band[r * row_bytes + x..][..tile_row].copy_from_slice(src);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The average of every block the slow way, to hold the integral image to.
+    fn brute_force_averages(image: &GrayImage, dw: u32, dh: u32) -> Vec<u8> {
+        let mut averages = Vec::new();
+        for by in 0..image.height() / dh {
+            for bx in 0..image.width() / dw {
+                let mut total = 0u64;
+                for y in by * dh..(by + 1) * dh {
+                    for x in bx * dw..(bx + 1) * dw {
+                        total += image.get_pixel(x, y)[0] as u64;
+                    }
+                }
+                averages.push((total / (dw as u64 * dh as u64)) as u8);
+            }
+        }
+        averages
+    }
+
+    #[test]
+    fn block_averages_match_brute_force() {
+        // Noise, and sizes the blocks don't divide, so the leftover edge gets skipped too
+        let mut state = 12345u32;
+        let image = GrayImage::from_fn(103, 77, |_, _| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            image::Luma([(state >> 24) as u8])
+        });
+        let integral = IntegralImage::new(&image);
+        for (dw, dh) in [(1, 1), (3, 3), (8, 8), (10, 7), (103, 77), (50, 1)] {
+            let (num_x, num_y, averages) = integral.block_averages(dw, dh);
+            assert_eq!((num_x, num_y), (103 / dw, 77 / dh));
+            assert_eq!(averages, brute_force_averages(&image, dw, dh), "{}x{} blocks", dw, dh);
+        }
+    }
+
+    #[test]
+    fn sums_past_u32_are_exact() {
+        // 255 * 4200 * 4100 is more than a u32 holds, the table wraps and the strips add it up
+        let image = GrayImage::from_pixel(4200, 4100, image::Luma([255]));
+        let integral = IntegralImage::new(&image);
+        assert_eq!(integral.sum(0, 0, 4200, 4100), 255 * 4200 * 4100);
+        assert_eq!(integral.block_averages(4200, 4100), (1, 1, vec![255]));
+    }
+
+    #[test]
+    fn grid_text_round_trip() {
+        let grid = DiceGrid {
+            width: 3,
+            height: 2,
+            cells: vec![DiceSides::One, DiceSides::Six, DiceSides::Three, DiceSides::Two, DiceSides::Five, DiceSides::Four],
+        };
+        let back = DiceGrid::from_text(&grid.to_text()).unwrap();
+        assert_eq!((back.width, back.height), (3, 2));
+        assert_eq!(back.cells, grid.cells);
+    }
+
+    #[test]
+    fn mosaics_too_big_for_a_u32_are_an_error() {
+        let grid = DiceGrid { width: 70_000, height: 1, cells: vec![DiceSides::One; 70_000] };
+        assert!(matches!(mosaic_size(&grid, (70_000, 10), 0), Err(DiceError::InvalidOptions(_))));
+        // The die plus the gap overflowing counts too
+        assert!(matches!(mosaic_size(&grid, (10, 10), u32::MAX), Err(DiceError::InvalidOptions(_))));
+        assert_eq!(mosaic_size(&grid, (10, 10), 2).unwrap(), (70_000 * 12 - 2, 10));
+    }
+
+    #[test]
+    fn grid_text_with_a_bad_face_is_an_error() {
+        assert!(matches!(DiceGrid::from_text("DICEGRID 1\nsize=3x1\n107\n"), Err(DiceError::Decode(_))));
+    }
+}
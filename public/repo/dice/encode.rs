@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, RgbaImage};
+
+use crate::error::DiceError;
+use crate::metadata::{RenderSettings, METADATA_KEY};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+/// File format the finished mosaic is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `indexed` writes a palette PNG. A dice mosaic only has a handful of colours so this is usually much smaller.
+    Png { compression: PngCompression, indexed: bool },
+    /// Quality 1-100. JPEG has no alpha, transparent pixels come out black.
+    Jpeg { quality: u8 },
+    /// Always lossless.
+    WebP,
+    /// Quality 1-100, speed 1 (slowest, smallest) - 10 (fastest).
+    Avif { quality: u8, speed: u8 },
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Png {
+            compression: PngCompression::Default,
+            indexed: false,
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png { .. } => "png",
+            OutputFormat::Jpeg { .. } => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif { .. } => "avif",
+        }
+    }
+
+    /// Only PNG has somewhere to keep the render settings (see metadata.rs), the others are written without.
+    pub fn keeps_settings(&self) -> bool {
+        matches!(self, OutputFormat::Png { .. })
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png { .. } => "image/png",
+            OutputFormat::Jpeg { .. } => "image/jpeg",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Avif { .. } => "image/avif",
+        }
+    }
+}
+
+/// Encodes the rendered mosaic into bytes of the requested format.
+/// `settings` is embedded as a tEXt chunk in PNG output so the render can be reproduced later.
+pub fn encode_image(
+    image: &RgbaImage,
+    format: &OutputFormat,
+    settings: Option<&RenderSettings>,
+) -> Result<Vec<u8>, DiceError> {
+    let (width, height) = image.dimensions();
+    let mut bytes = Vec::<u8>::new();
+
+    match *format {
+        OutputFormat::Png { compression, indexed } => {
+            let mut encoder = png_encoder(&mut bytes, (width, height), compression, settings)?;
+            if indexed {
+                let palette = Palette::from_pixels(image.as_raw());
+                palette.set_on(&mut encoder);
+                let indices = palette.indices(image.as_raw());
+                let mut writer = encoder.write_header().map_err(|e| DiceError::Encode(e.to_string()))?;
+                writer.write_image_data(&indices).map_err(|e| DiceError::Encode(e.to_string()))?;
+            } else {
+                encoder.set_color(png::ColorType::Rgba);
+                let mut writer = encoder.write_header().map_err(|e| DiceError::Encode(e.to_string()))?;
+                writer.write_image_data(image.as_raw()).map_err(|e| DiceError::Encode(e.to_string()))?;
+            }
+        }
+        OutputFormat::Jpeg { quality } => {
+            let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100))
+                .write_image(rgb.as_raw(), width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| DiceError::Encode(e.to_string()))?;
+        }
+        OutputFormat::WebP => {
+            WebPEncoder::new_lossless(&mut Cursor::new(&mut bytes))
+                .write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| DiceError::Encode(e.to_string()))?;
+        }
+        OutputFormat::Avif { quality, speed } => {
+            AvifEncoder::new_with_speed_quality(&mut bytes, speed.clamp(1, 10), quality.clamp(1, 100))
+                .write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| DiceError::Encode(e.to_string()))?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Writes a PNG a few rows at a time, for mosaics too big to hold in memory at once.
+/// `rows` gets a function taking the next whole rows (raw RGBA) and has to hand it every row, top to bottom.
+/// For a palette PNG `colours` has to hold (as raw RGBA) every colour the image will contain,
+/// the palette is fixed before the first row. It's ignored for a truecolour PNG.
+pub fn stream_png<W: Write>(
+    out: W,
+    size: (u32, u32),
+    compression: PngCompression,
+    indexed: bool,
+    colours: &[u8],
+    settings: Option<&RenderSettings>,
+    rows: impl FnOnce(&mut dyn FnMut(&[u8]) -> Result<(), DiceError>) -> Result<(), DiceError>,
+) -> Result<(), DiceError> {
+    let mut encoder = png_encoder(out, size, compression, settings)?;
+    let palette = if indexed {
+        let palette = Palette::from_pixels(colours);
+        palette.set_on(&mut encoder);
+        Some(palette)
+    } else {
+        encoder.set_color(png::ColorType::Rgba);
+        None
+    };
+
+    let mut writer = encoder.write_header().map_err(|e| DiceError::Encode(e.to_string()))?;
+    let mut stream = writer.stream_writer().map_err(|e| DiceError::Encode(e.to_string()))?;
+    rows(&mut |rgba: &[u8]| {
+        let result = match &palette {
+            Some(palette) => stream.write_all(&palette.indices(rgba)),
+            None => stream.write_all(rgba),
+        };
+        result.map_err(|e| DiceError::Encode(e.to_string()))
+    })?;
+    // Errors if fewer rows came than the header promised
+    stream.finish().map_err(|e| DiceError::Encode(e.to_string()))?;
+    writer.finish().map_err(|e| DiceError::Encode(e.to_string()))
+}
+
+fn png_encoder<W: Write>(
+    out: W,
+    (width, height): (u32, u32),
+    compression: PngCompression,
+    settings: Option<&RenderSettings>,
+) -> Result<png::Encoder<'static, W>, DiceError> {
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(match compression {
+        PngCompression::Fast => png::Compression::Fast,
+        PngCompression::Default => png::Compression::Balanced,
+        PngCompression::Best => png::Compression::High,
+    });
+    if let Some(settings) = settings {
+        encoder
+            .add_text_chunk(METADATA_KEY.to_string(), settings.to_text())
+            .map_err(|e| DiceError::Encode(e.to_string()))?;
+    }
+    Ok(encoder)
+}
+
+/// At most 256 colours, and which one each pixel gets.
+/// Exact when there are few enough colours (the usual case for dice), quantised otherwise.
+enum Palette {
+    Exact(Vec<[u8; 4]>, HashMap<[u8; 4], u8>),
+    Quantized(Vec<[u8; 4]>, color_quant::NeuQuant),
+}
+
+impl Palette {
+    /// Colours in order of first appearance in `rgba`.
+    fn from_pixels(rgba: &[u8]) -> Palette {
+        let mut colours: Vec<[u8; 4]> = Vec::new();
+        let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+        for p in rgba.chunks_exact(4) {
+            let p = [p[0], p[1], p[2], p[3]];
+            if lookup.contains_key(&p) {
+                continue;
+            }
+            if colours.len() == 256 {
+                return Palette::quantize(rgba);
+            }
+            lookup.insert(p, colours.len() as u8);
+            colours.push(p);
+        }
+        Palette::Exact(colours, lookup)
+    }
+
+    fn quantize(rgba: &[u8]) -> Palette {
+        let nq = color_quant::NeuQuant::new(10, 256, rgba);
+        let colours = nq
+            .color_map_rgba()
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]])
+            .collect();
+        Palette::Quantized(colours, nq)
+    }
+
+    fn colours(&self) -> &[[u8; 4]] {
+        match self {
+            Palette::Exact(colours, _) | Palette::Quantized(colours, _) => colours,
+        }
+    }
+
+    fn set_on<W: Write>(&self, encoder: &mut png::Encoder<W>) {
+        let rgb: Vec<u8> = self.colours().iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+        let alpha: Vec<u8> = self.colours().iter().map(|c| c[3]).collect();
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_palette(rgb);
+        if alpha.iter().any(|&a| a != 255) {
+            encoder.set_trns(alpha);
+        }
+    }
+
+    /// One index per pixel. A colour the exact palette doesn't have gets the nearest one.
+    fn indices(&self, rgba: &[u8]) -> Vec<u8> {
+        match self {
+            Palette::Exact(colours, lookup) => rgba
+                .chunks_exact(4)
+                .map(|p| {
+                    let p = [p[0], p[1], p[2], p[3]];
+                    lookup.get(&p).copied().unwrap_or_else(|| nearest(colours, p))
+                })
+                .collect(),
+            Palette::Quantized(_, nq) => rgba.chunks_exact(4).map(|p| nq.index_of(p) as u8).collect(),
+        }
+    }
+}
+
+fn nearest(colours: &[[u8; 4]], p: [u8; 4]) -> u8 {
+    let distance = |c: &[u8; 4]| (0..4).map(|i| (c[i] as i32 - p[i] as i32).pow(2)).sum::<i32>();
+    colours.iter().enumerate().min_by_key(|(_, c)| distance(c)).map_or(0, |(i, _)| i as u8)
+}
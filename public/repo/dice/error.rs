@@ -0,0 +1,82 @@
+use std::fmt;
+use std::path::Path;
+
+/// Everything that can go wrong turning a photo into dice, grouped so callers can tell failures apart.
+#[derive(Debug)]
+pub enum DiceError {
+    /// Reading or writing a file (or stdin / stdout) failed.
+    Io { path: String, source: std::io::Error },
+    /// An image, grid file, profile or embedded settings couldn't be understood.
+    Decode(String),
+    /// The face images don't make a usable set of six.
+    InvalidFaceSet(String),
+    /// Settings that make no sense, alone or together.
+    InvalidOptions(String),
+    /// The output couldn't be encoded.
+    Encode(String),
+    /// The user called it off.
+    Cancelled,
+}
+
+impl DiceError {
+    pub fn io(path: impl AsRef<Path>, source: std::io::Error) -> DiceError {
+        let path = path.as_ref();
+        let path = if path == Path::new("-") { "stdin/stdout".to_string() } else { path.display().to_string() };
+        DiceError::Io { path, source }
+    }
+
+    /// Failure from the image crate, which mixes file errors in with format errors.
+    pub fn image(path: impl AsRef<Path>, err: image::ImageError) -> DiceError {
+        match err {
+            image::ImageError::IoError(source) => DiceError::io(path, source),
+            err => DiceError::Decode(format!("{}: {}", path.as_ref().display(), err)),
+        }
+    }
+
+    /// Exit code for the CLI, so wrapper scripts can tell failures apart.
+    /// 2 is what clap exits with for bad arguments, so invalid options share it.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DiceError::InvalidOptions(_) => 2,
+            DiceError::Io { .. } => 3,
+            DiceError::Decode(_) => 4,
+            DiceError::InvalidFaceSet(_) => 5,
+            DiceError::Encode(_) => 6,
+            DiceError::Cancelled => 130, // what shells report for Ctrl+C
+        }
+    }
+
+    /// Short name of the kind of failure, e.g. for the JS error name.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DiceError::Io { .. } => "IoError",
+            DiceError::Decode(_) => "DecodeError",
+            DiceError::InvalidFaceSet(_) => "InvalidFaceSetError",
+            DiceError::InvalidOptions(_) => "InvalidOptionsError",
+            DiceError::Encode(_) => "EncodeError",
+            DiceError::Cancelled => "AbortError", // the name fetch() and friends use
+        }
+    }
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiceError::Io { path, source } => write!(f, "{}: {}", path, source),
+            DiceError::Decode(msg) => write!(f, "{}", msg),
+            DiceError::InvalidFaceSet(msg) => write!(f, "Bad face set: {}", msg),
+            DiceError::InvalidOptions(msg) => write!(f, "{}", msg),
+            DiceError::Encode(msg) => write!(f, "Couldn't encode the output: {}", msg),
+            DiceError::Cancelled => write!(f, "Cancelled, nothing was rendered"),
+        }
+    }
+}
+
+impl std::error::Error for DiceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DiceError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
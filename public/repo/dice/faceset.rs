@@ -0,0 +1,278 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::DiceError;
+
+/// Manifest file names looked for in a dice directory, first one found wins.
+pub const MANIFEST_NAMES: [&str; 2] = ["faces.toml", "faces.json"];
+
+/// `faces.toml` in a dice directory, says which file is which face.
+///
+/// ```toml
+/// name = "Classic"
+/// colour = "white"
+///
+/// [[face]]
+/// id = 1
+/// file = "one.png"
+/// tone = 229                       # optional, what the face should measure
+/// variants = ["one_b.png"]        # optional, more photos of the same face, mixed in with --seed
+/// ```
+///
+/// `variants` are photos, not turns: One, Four and Five are turned by the renderer, a Two, Three
+/// or Six the other way round is a different picture, so its orientations go in as variants.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    pub name: Option<String>,
+    pub colour: Option<String>, // colour of the dice this set shows, e.g. "white" or "black"
+    pub face: Vec<ManifestFace>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFace {
+    pub id: u8, // 1-6
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone: Option<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<String>, // other photos of this face, e.g. a Two turned a quarter
+}
+
+/// One face of a set, resolved to files on disk.
+#[derive(Debug, Clone)]
+pub struct FaceFile {
+    pub id: u8,
+    pub path: PathBuf,
+    pub tone: Option<u8>,
+    pub variants: Vec<PathBuf>,
+}
+
+/// The six faces of a dice directory, One first.
+#[derive(Debug, Clone)]
+pub struct FaceSet {
+    pub name: Option<String>,
+    pub colour: Option<String>,
+    pub faces: Vec<FaceFile>,
+    pub manifest: Option<PathBuf>, // None when the faces were picked by file name
+    pub ignored: Vec<PathBuf>,     // files in the directory that aren't faces
+}
+
+impl FaceSet {
+    /// Reads the manifest if there is one, otherwise takes the six images in the directory
+    /// in natural order (so `2.png` comes before `10.png`).
+    pub fn find(dir: &Path) -> Result<FaceSet, DiceError> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map_err(|e| DiceError::io(dir, e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b)));
+
+        match MANIFEST_NAMES.iter().map(|name| dir.join(name)).find(|p| p.is_file()) {
+            Some(manifest) => FaceSet::from_manifest(dir, &manifest, files),
+            None => FaceSet::from_names(dir, files),
+        }
+    }
+
+    fn from_manifest(dir: &Path, path: &Path, files: Vec<PathBuf>) -> Result<FaceSet, DiceError> {
+        let text = std::fs::read_to_string(path).map_err(|e| DiceError::io(path, e))?;
+        let manifest: Manifest = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        }
+        .map_err(|e| DiceError::Decode(format!("Bad face manifest {}: {}", path.display(), e)))?;
+
+        let mut faces = Vec::with_capacity(6);
+        for id in 1..=6u8 {
+            let mut entries = manifest.face.iter().filter(|f| f.id == id);
+            let entry = entries
+                .next()
+                .ok_or_else(|| DiceError::InvalidFaceSet(format!("{} has no face {}", path.display(), id)))?;
+            if entries.next().is_some() {
+                return Err(DiceError::InvalidFaceSet(format!(
+                    "{} lists face {} more than once",
+                    path.display(),
+                    id
+                )));
+            }
+
+            let resolve = |file: &str| {
+                let full = dir.join(file);
+                if full.is_file() {
+                    Ok(full)
+                } else {
+                    Err(DiceError::InvalidFaceSet(format!(
+                        "{} points face {} at {}, which doesn't exist",
+                        path.display(),
+                        id,
+                        file
+                    )))
+                }
+            };
+            faces.push(FaceFile {
+                id,
+                path: resolve(&entry.file)?,
+                tone: entry.tone,
+                variants: entry.variants.iter().map(|v| resolve(v)).collect::<Result<_, _>>()?,
+            });
+        }
+        if let Some(extra) = manifest.face.iter().find(|f| !(1..=6).contains(&f.id)) {
+            return Err(DiceError::InvalidFaceSet(format!(
+                "{} has a face {}, faces go from 1 to 6",
+                path.display(),
+                extra.id
+            )));
+        }
+
+        let used: Vec<&PathBuf> = faces.iter().flat_map(|f| std::iter::once(&f.path).chain(&f.variants)).collect();
+        let ignored = files
+            .into_iter()
+            .filter(|f| f.as_path() != path && !used.contains(&f))
+            .collect();
+
+        Ok(FaceSet {
+            name: manifest.name,
+            colour: manifest.colour,
+            faces,
+            manifest: Some(path.to_path_buf()),
+            ignored,
+        })
+    }
+
+    fn from_names(dir: &Path, files: Vec<PathBuf>) -> Result<FaceSet, DiceError> {
+        // .DS_Store, Thumbs.db, notes and the like aren't faces
+        let (images, ignored): (Vec<PathBuf>, Vec<PathBuf>) = files
+            .into_iter()
+            .partition(|path| !file_name(path).starts_with('.') && image::ImageFormat::from_path(path).is_ok());
+
+        if images.len() != 6 {
+            let found: Vec<String> = images.iter().map(|p| file_name(p)).collect();
+            return Err(DiceError::InvalidFaceSet(format!(
+                "You need exactly 6 dice images in {} (or a faces.toml saying which is which), found {}: {}",
+                dir.display(),
+                images.len(),
+                found.join(", ")
+            )));
+        }
+
+        Ok(FaceSet {
+            name: None,
+            colour: None,
+            faces: images
+                .into_iter()
+                .zip(1..)
+                .map(|(path, id)| FaceFile { id, path, tone: None, variants: Vec::new() })
+                .collect(),
+            manifest: None,
+            ignored,
+        })
+    }
+
+    /// Which file became which face, and why.
+    pub fn report(&self) -> String {
+        let mut out = match &self.manifest {
+            Some(path) => format!("Faces from {}", path.display()),
+            None => "Faces picked by file name (no faces.toml)".to_string(),
+        };
+        if let Some(name) = &self.name {
+            out += &format!(", set \"{}\"", name);
+        }
+        if let Some(colour) = &self.colour {
+            out += &format!(", {} dice", colour);
+        }
+        out.push('\n');
+
+        for face in &self.faces {
+            out += &format!("  {} <- {}", face.id, file_name(&face.path));
+            if !face.variants.is_empty() {
+                let variants: Vec<String> = face.variants.iter().map(|p| file_name(p)).collect();
+                out += &format!(" (+ {})", variants.join(", "));
+            }
+            out.push('\n');
+        }
+        if !self.ignored.is_empty() {
+            let ignored: Vec<String> = self.ignored.iter().map(|p| file_name(p)).collect();
+            out += &format!("  ignored: {}\n", ignored.join(", "));
+        }
+        out
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Compares file names the way people count, `die2` before `die10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+                let b_len = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+                let (a_num, b_num) = (a[..a_len].trim_start_matches('0'), b[..b_len].trim_start_matches('0'));
+                // Longer number is bigger, same length compares digit by digit
+                let order = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_sort_by_value() {
+        assert_eq!(natural_cmp("die2", "die10"), Ordering::Less);
+        assert_eq!(natural_cmp("die10.png", "die9.png"), Ordering::Greater);
+        // Leading zeros don't count, the rest of the name decides
+        assert_eq!(natural_cmp("die01", "die1"), Ordering::Equal);
+        assert_eq!(natural_cmp("die01.png", "die2.png"), Ordering::Less);
+        // Neither does case
+        assert_eq!(natural_cmp("Die3", "die3"), Ordering::Equal);
+        assert_eq!(natural_cmp("B1", "a2"), Ordering::Greater);
+        assert_eq!(natural_cmp("die", "die1"), Ordering::Less);
+    }
+
+    #[test]
+    fn listing_skips_what_isnt_a_face() {
+        let dir = std::env::temp_dir().join(format!("dice-faceset-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let names = ["die10.png", "die2.png", "die1.png", "die3.png", "Die4.png", "die5.png", ".DS_Store", "._die1.png", "notes.txt"];
+        for name in names {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let set = FaceSet::find(&dir).unwrap();
+        let faces: Vec<String> = set.faces.iter().map(|f| file_name(&f.path)).collect();
+        assert_eq!(faces, ["die1.png", "die2.png", "die3.png", "Die4.png", "die5.png", "die10.png"]);
+        let ignored: Vec<String> = set.ignored.iter().map(|p| file_name(p)).collect();
+        assert_eq!(ignored, ["._die1.png", ".DS_Store", "notes.txt"]);
+        assert!(set.manifest.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,465 @@
+//! C ABI (`ffi` feature) for embedding the renderer in C and C++ programs without a subprocess.
+//! Face sets and render jobs are opaque handles, every call that can fail returns a `DiceStatus` and
+//! `dice_last_error` has the message. dice.h is generated from this file, see cbindgen.toml.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::error::DiceError;
+use crate::profile::Profile;
+use crate::progress::{CancelToken, Hooks, Stage};
+use crate::renderer::DiceRenderer;
+use crate::tileset::{DirectoryTiles, MemoryTiles, ProceduralDice, TileSet};
+
+/// What a call came to. Failures have the numbers the CLI exits with.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiceStatus {
+    Ok = 0,
+    /// Settings that make no sense, also null pointers and text that isn't UTF-8.
+    InvalidOptions = 2,
+    Io = 3,
+    /// A photo or the settings couldn't be understood.
+    Decode = 4,
+    InvalidFaceSet = 5,
+    Encode = 6,
+    /// A bug in the library, the message says where.
+    Panic = 101,
+    /// The job was cancelled with `dice_job_cancel`.
+    Cancelled = 130,
+}
+
+/// What a job is busy with, for the progress callback.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiceStage {
+    Analysing,
+    Rendering,
+    Encoding,
+}
+
+/// Called on the rendering thread between rows of dice: `done` out of `total` rows of the stage.
+pub type DiceProgressCallback = Option<extern "C" fn(stage: DiceStage, done: u32, total: u32, user_data: *mut c_void)>;
+
+/// Tiles to draw with, darkest first. Can make any number of jobs and is freed with `dice_face_set_free`.
+pub struct DiceFaceSet {
+    tiles: Box<dyn TileSet + Send + Sync>,
+}
+
+/// A renderer for one set of settings, for any number of photos until it's cancelled.
+/// Freed with `dice_job_free`.
+pub struct DiceJob {
+    renderer: DiceRenderer,
+    cancel: CancelToken,
+    progress: DiceProgressCallback,
+    user_data: *mut c_void,
+}
+
+/// Bytes owned by the library, freed with `dice_buffer_free`.
+#[repr(C)]
+pub struct DiceBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// RGBA pixels, `width * 4` bytes a row, owned by the library and freed with `dice_image_free`.
+#[repr(C)]
+pub struct DiceImage {
+    pub pixels: *mut u8,
+    pub width: u32,
+    pub height: u32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, turning an error or a panic into a status and keeping its message for `dice_last_error`.
+fn guard(f: impl FnOnce() -> Result<(), DiceError>) -> DiceStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return DiceStatus::Ok,
+        Ok(Err(err)) => (status_of(&err), err.to_string()),
+        Err(panic) => (DiceStatus::Panic, panic_message(panic)),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message.replace('\0', " ")).ok());
+    status
+}
+
+fn status_of(err: &DiceError) -> DiceStatus {
+    match err {
+        DiceError::InvalidOptions(_) => DiceStatus::InvalidOptions,
+        DiceError::Io { .. } => DiceStatus::Io,
+        DiceError::Decode(_) => DiceStatus::Decode,
+        DiceError::InvalidFaceSet(_) => DiceStatus::InvalidFaceSet,
+        DiceError::Encode(_) => DiceStatus::Encode,
+        DiceError::Cancelled => DiceStatus::Cancelled,
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let what = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    format!("Panicked, this is a bug: {}", what)
+}
+
+fn null(what: &str) -> DiceError {
+    DiceError::InvalidOptions(format!("{} is null", what))
+}
+
+/// # Safety
+/// `ptr` is null or a NUL-terminated string.
+unsafe fn text<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, DiceError> {
+    if ptr.is_null() {
+        return Err(null(what));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| DiceError::InvalidOptions(format!("{} isn't UTF-8", what)))
+}
+
+/// # Safety
+/// `data` is null or points at `len` readable values.
+unsafe fn slice<'a, T>(data: *const T, len: usize, what: &str) -> Result<&'a [T], DiceError> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(null(what)),
+        (false, _) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+/// The message of the last call on this thread that failed, NULL if none has.
+/// It stays valid until the next call on this thread fails.
+#[no_mangle]
+pub extern "C" fn dice_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+}
+
+/// Version of the library, e.g. "0.1.0".
+#[no_mangle]
+pub extern "C" fn dice_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Opens a dice directory, picked the way the CLI does (faces.toml, otherwise natural order).
+///
+/// # Safety
+/// `dir` is a NUL-terminated UTF-8 path and `out` points at writable space for the handle.
+#[no_mangle]
+pub unsafe extern "C" fn dice_face_set_open(dir: *const c_char, out: *mut *mut DiceFaceSet) -> DiceStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        let tiles = DirectoryTiles::open(Path::new(text(dir, "dir")?))?;
+        *out = Box::into_raw(Box::new(DiceFaceSet { tiles: Box::new(tiles) }));
+        Ok(())
+    })
+}
+
+/// A face set from `count` encoded images (PNG, JPEG, ...) in memory, One first.
+/// The bytes are decoded straight away and needn't outlive the call.
+///
+/// # Safety
+/// `images` and `lengths` point at `count` entries, `images[i]` at `lengths[i]` readable bytes,
+/// and `out` at writable space for the handle.
+#[no_mangle]
+pub unsafe extern "C" fn dice_face_set_from_images(
+    images: *const *const u8,
+    lengths: *const usize,
+    count: usize,
+    out: *mut *mut DiceFaceSet,
+) -> DiceStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        let (images, lengths) = (slice(images, count, "images")?, slice(lengths, count, "lengths")?);
+        let faces = images
+            .iter()
+            .zip(lengths)
+            .map(|(&image, &len)| slice(image, len, "image"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tiles = MemoryTiles::new(&faces)?;
+        *out = Box::into_raw(Box::new(DiceFaceSet { tiles: Box::new(tiles) }));
+        Ok(())
+    })
+}
+
+/// Dice drawn at whatever size they're needed, no photos. Never fails.
+#[no_mangle]
+pub extern "C" fn dice_face_set_procedural() -> *mut DiceFaceSet {
+    Box::into_raw(Box::new(DiceFaceSet { tiles: Box::new(ProceduralDice::default()) }))
+}
+
+/// # Safety
+/// `faces` is NULL or a handle from a `dice_face_set_` call, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dice_face_set_free(faces: *mut DiceFaceSet) {
+    if !faces.is_null() {
+        drop(Box::from_raw(faces));
+    }
+}
+
+/// A job rendering with `faces` and `settings`, a profile as JSON or TOML text of any version
+/// (`dice schema` prints its JSON Schema). NULL settings take the defaults; NULL faces use the
+/// profile's dice-dir. The face set can be freed once the job is made.
+///
+/// # Safety
+/// `faces` is NULL or a live face set, `settings` NULL or a NUL-terminated string, and `out`
+/// points at writable space for the handle.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_new(
+    faces: *const DiceFaceSet,
+    settings: *const c_char,
+    out: *mut *mut DiceJob,
+) -> DiceStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(null("out"));
+        }
+        let profile = if settings.is_null() {
+            Profile::default()
+        } else {
+            Profile::from_text(text(settings, "settings")?)?
+        };
+        let options = profile.render_options()?;
+        let renderer = match (faces.as_ref(), &profile.dice_dir) {
+            (Some(faces), _) => DiceRenderer::from_tile_set(options, faces.tiles.as_ref())?,
+            (None, Some(dir)) => DiceRenderer::from_face_dir(options, Path::new(dir))?.0,
+            (None, None) => return Err(DiceError::InvalidFaceSet("no faces given and no dice-dir in the settings".to_string())),
+        };
+        let job = DiceJob { renderer, cancel: CancelToken::new(), progress: None, user_data: std::ptr::null_mut() };
+        *out = Box::into_raw(Box::new(job));
+        Ok(())
+    })
+}
+
+/// Calls `callback` with `user_data` between rows of dice while the job renders, NULL to stop.
+///
+/// # Safety
+/// `job` is a live job that isn't rendering.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_set_progress(job: *mut DiceJob, callback: DiceProgressCallback, user_data: *mut c_void) {
+    if let Some(job) = job.as_mut() {
+        job.progress = callback;
+        job.user_data = user_data;
+    }
+}
+
+/// Stops the job: a render in progress returns `DICE_STATUS_CANCELLED` after its current rows,
+/// and so does every render after. The one call that's safe from another thread while the job renders.
+///
+/// # Safety
+/// `job` is a live job.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_cancel(job: *const DiceJob) {
+    if let Some(job) = job.as_ref() {
+        job.cancel.cancel();
+    }
+}
+
+/// Renders an encoded photo (any format the image crate reads) into `out`, encoded in the settings'
+/// format (PNG unless changed) with the settings embedded.
+///
+/// # Safety
+/// `job` is a live job, `photo` points at `len` readable bytes and `out` at a writable `DiceBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_render(job: *const DiceJob, photo: *const u8, len: usize, out: *mut DiceBuffer) -> DiceStatus {
+    guard(|| {
+        let (job, out) = (job.as_ref().ok_or_else(|| null("job"))?, out.as_mut().ok_or_else(|| null("out"))?);
+        let photo = slice(photo, len, "photo")?;
+        let encoded = job.renderer.process(photo, &mut job.hooks())?.into_boxed_slice();
+        *out = DiceBuffer { len: encoded.len(), data: Box::into_raw(encoded).cast() };
+        Ok(())
+    })
+}
+
+/// Renders an encoded photo into RGBA pixels in `out`, e.g. to put straight on screen.
+///
+/// # Safety
+/// `job` is a live job, `photo` points at `len` readable bytes and `out` at a writable `DiceImage`.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_render_rgba(job: *const DiceJob, photo: *const u8, len: usize, out: *mut DiceImage) -> DiceStatus {
+    guard(|| {
+        let (job, out) = (job.as_ref().ok_or_else(|| null("job"))?, out.as_mut().ok_or_else(|| null("out"))?);
+        let photo = slice(photo, len, "photo")?;
+        let mut hooks = job.hooks();
+        let grid = job.renderer.grid_for(photo, &mut hooks)?;
+        let image = job.renderer.render(&grid, &mut hooks)?;
+        let (width, height) = image.dimensions();
+        let pixels = image.into_raw().into_boxed_slice();
+        *out = DiceImage { pixels: Box::into_raw(pixels).cast(), width, height };
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `job` is NULL or a job from `dice_job_new` that isn't rendering, not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dice_job_free(job: *mut DiceJob) {
+    if !job.is_null() {
+        drop(Box::from_raw(job));
+    }
+}
+
+/// Frees the bytes and empties the buffer, freeing an empty buffer does nothing.
+///
+/// # Safety
+/// `buffer` is NULL or a buffer filled in by the library.
+#[no_mangle]
+pub unsafe extern "C" fn dice_buffer_free(buffer: *mut DiceBuffer) {
+    if let Some(buffer) = buffer.as_mut() {
+        if !buffer.data.is_null() {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+        }
+        *buffer = DiceBuffer { data: std::ptr::null_mut(), len: 0 };
+    }
+}
+
+/// Frees the pixels and empties the image, freeing an empty image does nothing.
+///
+/// # Safety
+/// `image` is NULL or an image filled in by the library.
+#[no_mangle]
+pub unsafe extern "C" fn dice_image_free(image: *mut DiceImage) {
+    if let Some(image) = image.as_mut() {
+        if !image.pixels.is_null() {
+            let len = image.width as usize * image.height as usize * 4;
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(image.pixels, len)));
+        }
+        *image = DiceImage { pixels: std::ptr::null_mut(), width: 0, height: 0 };
+    }
+}
+
+impl DiceJob {
+    fn hooks(&self) -> Hooks<'_> {
+        let mut hooks = Hooks::none().cancel_with(self.cancel.clone());
+        if let Some(callback) = self.progress {
+            let user_data = self.user_data;
+            hooks = hooks.on_progress(move |p| {
+                let stage = match p.stage {
+                    Stage::Analysing => DiceStage::Analysing,
+                    Stage::Rendering => DiceStage::Rendering,
+                    Stage::Encoding => DiceStage::Encoding,
+                };
+                callback(stage, p.done, p.total, user_data);
+            });
+        }
+        hooks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::{null, null_mut};
+
+    fn last_error() -> String {
+        let message = dice_last_error();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }.to_str().unwrap().to_string()
+    }
+
+    /// A small photo as PNG bytes.
+    fn photo() -> Vec<u8> {
+        let image = image::GrayImage::from_fn(40, 40, |x, y| image::Luma([((x + y) * 3) as u8]));
+        let mut bytes = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    /// A job with procedural faces, or the status it failed with.
+    fn job(settings: &CStr) -> Result<*mut DiceJob, DiceStatus> {
+        unsafe {
+            let faces = dice_face_set_procedural();
+            let mut job = null_mut();
+            let status = dice_job_new(faces, settings.as_ptr(), &mut job);
+            dice_face_set_free(faces);
+            match status {
+                DiceStatus::Ok => Ok(job),
+                status => Err(status),
+            }
+        }
+    }
+
+    #[test]
+    fn null_pointers_are_invalid_options() {
+        unsafe {
+            assert_eq!(dice_face_set_open(c"dice".as_ptr(), null_mut()), DiceStatus::InvalidOptions);
+            assert!(last_error().contains("out is null"));
+            let mut faces = null_mut();
+            assert_eq!(dice_face_set_open(null(), &mut faces), DiceStatus::InvalidOptions);
+            assert!(faces.is_null());
+        }
+    }
+
+    #[test]
+    fn missing_directory_is_io() {
+        let mut faces = null_mut();
+        let status = unsafe { dice_face_set_open(c"/no/such/dice/dir".as_ptr(), &mut faces) };
+        assert_eq!(status, DiceStatus::Io);
+        assert!(last_error().contains("/no/such/dice/dir"));
+    }
+
+    #[test]
+    fn unusable_faces_are_invalid_face_set() {
+        unsafe {
+            // Not six faces, so no job can use them
+            let mut faces = null_mut();
+            assert_eq!(dice_face_set_from_images(null(), null(), 0, &mut faces), DiceStatus::Ok);
+            let mut job = null_mut();
+            assert_eq!(dice_job_new(faces, null(), &mut job), DiceStatus::InvalidFaceSet);
+            dice_face_set_free(faces);
+
+            let garbage = b"not an image".as_ptr();
+            let len = 12;
+            assert_eq!(dice_face_set_from_images(&garbage, &len, 1, &mut faces), DiceStatus::InvalidFaceSet);
+
+            assert_eq!(dice_job_new(null(), c"dice-size = 8".as_ptr(), &mut job), DiceStatus::InvalidFaceSet);
+            assert!(last_error().contains("dice-dir"));
+        }
+    }
+
+    #[test]
+    fn bad_settings_are_invalid_options_or_decode() {
+        assert_eq!(job(c"version = 1\npreset = \"sepia\"").unwrap_err(), DiceStatus::InvalidOptions);
+        assert!(last_error().contains("sepia"));
+        assert_eq!(job(c"dice-size = [").unwrap_err(), DiceStatus::Decode);
+        assert_eq!(job(c"version = 99").unwrap_err(), DiceStatus::Decode);
+        assert_eq!(job(c"{\"colour\": \"red\"}").unwrap_err(), DiceStatus::Decode);
+        assert_eq!(job(c"\xff").unwrap_err(), DiceStatus::InvalidOptions);
+    }
+
+    #[test]
+    fn renders_and_reports_what_went_wrong() {
+        let job = job(c"dice-size = 4").unwrap();
+        let photo = photo();
+        unsafe {
+            let mut out = DiceBuffer { data: null_mut(), len: 0 };
+            assert_eq!(dice_job_render(job, photo.as_ptr(), photo.len(), &mut out), DiceStatus::Ok);
+            assert!(std::slice::from_raw_parts(out.data, out.len).starts_with(b"\x89PNG"));
+            dice_buffer_free(&mut out);
+            assert!(out.data.is_null());
+
+            let truncated = &photo[..photo.len() / 2];
+            assert_eq!(dice_job_render(job, truncated.as_ptr(), truncated.len(), &mut out), DiceStatus::Decode);
+            assert_eq!(dice_job_render(job, null(), 10, &mut out), DiceStatus::InvalidOptions);
+
+            let mut image = DiceImage { pixels: null_mut(), width: 0, height: 0 };
+            assert_eq!(dice_job_render_rgba(job, photo.as_ptr(), photo.len(), &mut image), DiceStatus::Ok);
+            assert_eq!((image.width, image.height), (40, 40));
+            dice_image_free(&mut image);
+
+            dice_job_cancel(job);
+            assert_eq!(dice_job_render(job, photo.as_ptr(), photo.len(), &mut out), DiceStatus::Cancelled);
+            assert!(out.data.is_null());
+            dice_job_free(job);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::error::DiceError;
+
+/// First line of every grid file. Bump the number if the layout ever changes.
+const HEADER: &str = "DICEGRID 1";
+
+/// Writes a grid of faces (1-6, row by row) as text:
+///
+/// ```text
+/// DICEGRID 1
+/// size=4x2
+/// 1234
+/// 6543
+/// ```
+pub fn write_grid(width: u32, height: u32, faces: &[u8]) -> String {
+    let mut out = format!("{}\nsize={}x{}\n", HEADER, width, height);
+    for row in faces.chunks(width.max(1) as usize) {
+        out.extend(row.iter().map(|&f| (b'0' + f) as char));
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads a grid file back into (width, height, faces 1-6 row by row).
+pub fn parse_grid(text: &str) -> Result<(u32, u32, Vec<u8>), DiceError> {
+    let bad = |msg: String| DiceError::Decode(format!("Bad grid file: {}", msg));
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+
+    if lines.next() != Some(HEADER) {
+        return Err(bad(format!("expected \"{}\" on the first line", HEADER)));
+    }
+
+    let size = lines
+        .next()
+        .and_then(|l| l.strip_prefix("size="))
+        .ok_or_else(|| bad("missing its size line".to_string()))?;
+    let (width, height) = size
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)))
+        .ok_or_else(|| bad(format!("size {} isn't WIDTHxHEIGHT", size)))?;
+
+    // The size is only what the file claims: it has to fit the u32 cell indexes, and nothing gets
+    // allocated for it up front, the faces grow with the rows that are actually there
+    let cells = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&cells| cells <= u32::MAX as usize)
+        .ok_or_else(|| bad(format!("{}x{} is too many dice", width, height)))?;
+    let mut faces = Vec::new();
+    for (y, line) in lines.enumerate() {
+        if line.len() != width as usize {
+            return Err(bad(format!("row {} has {} dice, expected {}", y + 1, line.len(), width)));
+        }
+        for c in line.bytes() {
+            match c {
+                b'1'..=b'6' => faces.push(c - b'0'),
+                _ => return Err(bad(format!("row {} has an invalid face '{}'", y + 1, c as char))),
+            }
+        }
+    }
+
+    if faces.len() != cells {
+        return Err(bad(format!(
+            "{} rows, expected {}",
+            faces.len() / width.max(1) as usize,
+            height
+        )));
+    }
+
+    Ok((width, height, faces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_bad(text: &str) -> bool {
+        matches!(parse_grid(text), Err(DiceError::Decode(_)))
+    }
+
+    #[test]
+    fn round_trip() {
+        let faces = vec![1, 2, 3, 4, 6, 5, 4, 3];
+        let text = write_grid(4, 2, &faces);
+        assert_eq!(text, "DICEGRID 1\nsize=4x2\n1234\n6543\n");
+        assert_eq!(parse_grid(&text).unwrap(), (4, 2, faces));
+    }
+
+    #[test]
+    fn blank_lines_and_indentation_are_fine() {
+        assert_eq!(parse_grid("\n  DICEGRID 1\nsize=2x1\n\n 16 \n").unwrap(), (2, 1, vec![1, 6]));
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(is_bad(""));
+        assert!(is_bad("DICEGRID 2\nsize=1x1\n1\n"));
+        assert!(is_bad("DICEGRID 1\n1\n"));
+        assert!(is_bad("DICEGRID 1\nsize=1by1\n1\n"));
+        assert!(is_bad("DICEGRID 1\nsize=-1x1\n1\n"));
+        assert!(is_bad("DICEGRID 1\nsize=2x1\n123\n"));
+        assert!(is_bad("DICEGRID 1\nsize=2x1\n17\n"));
+        assert!(is_bad("DICEGRID 1\nsize=2x2\n12\n"));
+        assert!(is_bad("DICEGRID 1\nsize=2x1\n12\n34\n"));
+    }
+
+    #[test]
+    fn overflowing_sizes_are_rejected() {
+        // 65536 * 65537 wraps to 65536 in u32, which one full row would have matched
+        let row = "1".repeat(65536);
+        assert!(is_bad(&format!("DICEGRID 1\nsize=65536x65537\n{}\n", row)));
+        assert!(is_bad(&format!("DICEGRID 1\nsize={}x{}\n1\n", u32::MAX, u32::MAX)));
+    }
+
+    #[test]
+    fn huge_sizes_need_the_rows_to_be_there() {
+        // Would reserve about 3.6 GB if the header sized the allocation
+        assert!(is_bad("DICEGRID 1\nsize=60000x60000\n"));
+    }
+}
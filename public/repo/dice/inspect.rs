@@ -0,0 +1,78 @@
+use image::{DynamicImage, GrayImage};
+
+use crate::dicelib::{map_intensity_to_dice_side, DiceGrid, DiceSides, IntensityPreset};
+use crate::tileset::tone;
+
+const SIDES: [DiceSides; 6] = [
+    DiceSides::One,
+    DiceSides::Two,
+    DiceSides::Three,
+    DiceSides::Four,
+    DiceSides::Five,
+    DiceSides::Six,
+];
+
+const BAR_WIDTH: usize = 40;
+
+fn bar(value: u64, max: u64) -> String {
+    let len = if max == 0 { 0 } else { (value * BAR_WIDTH as u64).div_ceil(max) as usize };
+    "#".repeat(len)
+}
+
+/// How many of each face the grid uses, One first.
+pub fn face_counts(grid: &DiceGrid) -> [u64; 6] {
+    let mut counts = [0u64; 6];
+    for side in &grid.cells {
+        counts[side.number() as usize - 1] += 1;
+    }
+    counts
+}
+
+/// Face usage table, e.g. for `stats` or the bill of materials.
+pub fn usage_table(grid: &DiceGrid) -> String {
+    let counts = face_counts(grid);
+    let total: u64 = counts.iter().sum();
+    let max = counts.iter().copied().max().unwrap_or(0);
+
+    let mut out = format!("Grid: {}x{} dice, {} total\n", grid.width, grid.height, total);
+    for (side, count) in SIDES.iter().zip(counts) {
+        let percent = if total == 0 { 0.0 } else { count as f32 * 100.0 / total as f32 };
+        out += &format!("{} {:>8} {:>6.1}%  {}\n", side.number(), count, percent, bar(count, max));
+    }
+    out
+}
+
+/// Bill of materials as CSV, one line per face plus the total.
+pub fn bom_csv(grid: &DiceGrid) -> String {
+    let counts = face_counts(grid);
+    let mut out = String::from("face,count\n");
+    for (side, count) in SIDES.iter().zip(counts) {
+        out += &format!("{},{}\n", side.number(), count);
+    }
+    out += &format!("total,{}\n", counts.iter().sum::<u64>());
+    out
+}
+
+/// Tone histogram of the prepared input in 16 buckets, with the faces each bucket maps to.
+pub fn histogram(input: &GrayImage, preset: &IntensityPreset) -> String {
+    let mut buckets = [0u64; 16];
+    for p in input.pixels() {
+        buckets[p[0] as usize / 16] += 1;
+    }
+    let max = buckets.iter().copied().max().unwrap_or(0);
+
+    let mut out = String::from("Tone      Faces  Pixels\n");
+    for (i, &count) in buckets.iter().enumerate() {
+        let (lo, hi) = (i as u8 * 16, i as u8 * 16 + 15);
+        let first = map_intensity_to_dice_side(lo, preset).number();
+        let last = map_intensity_to_dice_side(hi, preset).number();
+        let faces = if first == last { first.to_string() } else { format!("{}-{}", first, last) };
+        out += &format!("{:>3}-{:<3}  {:<5} {:>8}  {}\n", lo, hi, faces, count, bar(count, max));
+    }
+    out
+}
+
+/// Average brightness of a face image, transparent pixels count as black.
+pub fn face_tone(image: &DynamicImage) -> u8 {
+    tone(&image.to_rgba8())
+}
@@ -1,350 +1,1138 @@
 // use std::env; // For grabbing command-line arguments... no need for clap really.
-use std::path::Path; // Handy for working with file paths
-use image::{imageops, GrayImage, RgbaImage}; // Just the essentials for image processing
-mod dicelib;
-use dicelib::{add_reference_text, map_intensity_to_dice_side, load_image, Dice, DiceSides, IntensityPreset};
+use std::hash::BuildHasher;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf}; // Handy for working with file paths
+use clap::{Arg, ArgAction, ArgMatches};
+use image::{imageops, GrayImage}; // Just the essentials for image processing
+mod batch;
+mod progressbar;
+mod prompt;
+mod termimage;
+mod textout;
+mod tune;
+mod watch;
+// Everything but the terminal side lives in the library, see lib.rs
+use dice::{deepzoom, dicelib, encode, error, faceset, inspect, metadata, profile, progress};
+use dice::{DiceRenderer, Hooks, RenderOptions};
+use dicelib::{load_image, parse_thresholds, render_grid, DiceGrid, IntensityPreset};
+use batch::{collect_inputs, is_batch_input, output_names, print_summary, run_batch, JobOutcome};
+use deepzoom::write_deep_zoom;
+use encode::{encode_image, OutputFormat};
+use error::DiceError;
+use faceset::FaceSet;
+use inspect::{bom_csv, face_tone, histogram, usage_table};
+use metadata::read_settings;
+use profile::Profile;
+use progress::Stage;
+use progressbar::progress_bar;
+use prompt::Prompter;
+use termimage::{show_image, Graphics};
+use textout::{dice_size_for_columns, render_text, TextStyle};
+use tune::{face_colours, tune, Tuning};
+use watch::{Watched, POLL_INTERVAL};
+
+/// Outputs bigger than this (bytes of RGBA) are streamed even without --stream.
+const STREAM_ABOVE: u64 = 1 << 30;
+
+/// Size the faces get drawn at in an image preview, small so it's quick.
+const PREVIEW_FACE_SIZE: u32 = 12;
+
+/// The subcommands that work on a photo (or saved grid). `faces` only needs the face set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Render,  // full size image, tile pyramid or batch
+    Plan,    // grid file + bill of materials, no faces needed
+    Stats,   // histogram and face usage, no faces needed
+    Preview, // quick terminal or low-res image preview
+}
+
+impl Command {
+    fn needs_faces(&self) -> bool {
+        matches!(self, Command::Render | Command::Preview)
+    }
+}
 
 struct Images {
     input: GrayImage,
-    dice: [Dice; 6] // Six dice, one for each side. Simple and clean.
-}
-
-fn load_dice_images_d(dice_dir: &str) -> [Dice; 6] {
-    // Read the dice directory and grab all the files
-    let mut dice_image_paths: Vec<_> = std::fs::read_dir(dice_dir)
-        .expect("Couldn't find the dice directory. Double-check your path!")
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file())
-        .collect();
-
-    // Make sure we have exactly 6 dice images. No more, no less.
-    if dice_image_paths.len() != 6 {
-        panic!("You need exactly 6 dice images. No shortcuts allowed!");
-    }
-
-    // Sort the paths to keep things consistent (alphabetical order is nice)
-    dice_image_paths.sort();
-
-    // Ask the user for the dice size
-    println!("Enter the dice size you want (e.g., 32 for 32x32 pixels):");
-    let mut d_size_input = String::new();
-    std::io::stdin().read_line(&mut d_size_input).unwrap();
-    let d_size: u32 = match d_size_input.trim_end().parse() {
-        Ok(size) if size > 0 => size,
-        _ => {
-            println!("Invalid size. Defaulting to 32x32.");
-            32
-        }
-    };
+    renderer: DiceRenderer, // Options and faces. No faces for plan and stats, they never draw any.
+    dice_dir: Option<String>,
+    text: Option<TextStyle>, // Print to the terminal instead of rendering an image
+    deep_zoom: bool, // Write a tile pyramid instead of one big PNG
+    stream: bool, // Write the PNG band by band even when it'd fit in memory
+    grid: Option<DiceGrid>, // Saved grid to render instead of analysing an input image
+    save_grid: Option<String>, // Where to save the grid for re-rendering later
+    output: Option<String>, // Where the output goes (the output directory in batch mode)
+    batch: Vec<PathBuf>, // Photos to render in batch mode, empty for a single image
+    name_template: String, // Batch output file names
+    jobs: usize, // Batch worker threads
+    show: Option<Graphics>, // Draw the result in the terminal after saving it
+    prompter: Prompter,
+}
 
-    // Load and resize the dice images
-    let mut dice_array: [Option<Dice>; 6] = Default::default();
-    for (i, image_path) in dice_image_paths.iter().enumerate() {
-        let image_data = std::fs::read(image_path)
-            .unwrap_or_else(|_| panic!("Couldn't read dice image at {:?}", image_path));
-        let image = image::load_from_memory(&image_data)
-            .expect(&format!("Couldn't load dice image {:?}", image_path));
+fn arg<T: Clone + Send + Sync + 'static>(matches: &ArgMatches, id: &str) -> Option<T> {
+    matches.try_get_one::<T>(id).ok().flatten().cloned()
+}
 
-        // Resize the dice image to the user-specified size
-        let resized_image = image.resize_exact(d_size, d_size, imageops::FilterType::Lanczos3);
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+    arg::<bool>(matches, id).unwrap_or(false)
+}
 
-        dice_array[i] = Some(Dice {
-            side: match i {
-                0 => DiceSides::One,
-                1 => DiceSides::Two,
-                2 => DiceSides::Three,
-                3 => DiceSides::Four,
-                4 => DiceSides::Five,
-                5 => DiceSides::Six,
-                _ => unreachable!(), // This should never happen. If it does, something's seriously wrong.
-            },
-            image: resized_image,
-        });
-    }
+fn bool_arg(id: &'static str, long: &'static str, help: &'static str) -> Arg {
+    Arg::new(id)
+        .long(long)
+        .value_name("BOOL")
+        .help(help)
+        .value_parser(clap::value_parser!(bool))
+        .num_args(0..=1)
+        .default_missing_value("true")
+}
+
+/// Where the photo comes from and how it's turned into a grid. Every photo subcommand has these.
+fn input_args() -> Vec<Arg> {
+    vec![
+        Arg::new("input")
+            .short('i')
+            .long("input")
+            .value_name("INPUT_FILE")
+            .help("Path to the input image file (- for stdin), or a directory / glob for batch mode")
+            .required_unless_present("grid")
+            .num_args(1),
+        Arg::new("grid")
+            .short('g')
+            .long("grid")
+            .value_name("GRID_FILE")
+            .help("Use a grid saved with --save-grid (or by plan) instead of an input image")
+            .num_args(1),
+        Arg::new("dice_size")
+            .short('s')
+            .long("dice-size")
+            .value_name("PIXELS")
+            .help("Size of each die in pixels [default: 32]")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .num_args(1),
+        bool_arg("invert_input", "invert-input", "Invert the input image"),
+        Arg::new("preset")
+            .short('p')
+            .long("preset")
+            .value_name("PRESET")
+            .help("Intensity preset [default: default]")
+            .value_parser(["default", "high-contrast", "low-contrast", "bright", "dark"])
+            .num_args(1),
+        Arg::new("thresholds")
+            .long("thresholds")
+            .value_name("T1,T2,T3,T4,T5")
+            .help("Custom thresholds instead of a preset: the highest tone for faces One to Five, e.g. 40,90,140,190,225")
+            .value_parser(parse_thresholds)
+            .num_args(1),
+        Arg::new("output_size")
+            .long("output-size")
+            .value_name("WIDTHxHEIGHT")
+            .help("Fit the input onto a canvas of this size first, e.g. 1920x1080")
+            .value_parser(parse_size)
+            .num_args(1),
+        Arg::new("config")
+            .short('c')
+            .long("config")
+            .value_name("PROFILE")
+            .help("Load settings from a TOML or JSON profile (flags still override)")
+            .num_args(1),
+        Arg::new("save_config")
+            .long("save-config")
+            .value_name("PROFILE")
+            .help("Save the settings this run ends up using as a TOML or JSON profile")
+            .num_args(1),
+        Arg::new("reuse")
+            .long("reuse")
+            .value_name("PNG")
+            .help("Use the settings embedded in a PNG made earlier (flags still override)")
+            .num_args(1),
+        Arg::new("interactive")
+            .long("interactive")
+            .help("Ask for anything not given as a flag (the default when stdin is a terminal)")
+            .action(ArgAction::SetTrue),
+        Arg::new("no_interactive")
+            .long("no-interactive")
+            .help("Never ask, use defaults for anything not given as a flag")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("interactive"),
+        Arg::new("threads")
+            .long("threads")
+            .value_name("N")
+            .help("Threads for analysing and drawing rows of dice, 1 for none (same output either way) [default: number of CPUs]")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .num_args(1),
+    ]
+}
 
-    // Convert the Option array into a proper array. No room for None here.
-    core::array::from_fn(|i| dice_array[i].clone().expect("Failed to load dice images."))
+/// The face set.
+fn face_args() -> Vec<Arg> {
+    vec![
+        Arg::new("dice_dir")
+            .short('d')
+            .long("dice-dir")
+            .value_name("DICE_DIRECTORY")
+            .help("Directory with the six face images, or a faces.toml manifest saying which is which")
+            .num_args(1),
+        bool_arg("invert_dice", "invert-dice", "Invert the dice colours"),
+    ]
 }
 
-fn load_images_dynamic() -> Images {
+fn render_args() -> Vec<Arg> {
+    let mut args = input_args();
+    args.extend(face_args());
+    args.extend([
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("OUTPUT_FILE")
+            .help("Where to save the result, - for stdout [default: output/dice_output.<format>]")
+            .num_args(1),
+        bool_arg("debug_overlay", "debug-overlay", "Stamp dice size, count and image size onto the output"),
+        Arg::new("deep_zoom")
+            .long("deep-zoom")
+            .help("Write a DeepZoom tile pyramid (<output>.dzi + <output>_files/) instead of a single image")
+            .action(ArgAction::SetTrue),
+        Arg::new("stream")
+            .long("stream")
+            .help("Write the PNG a row of dice at a time instead of building the whole image first (automatic for huge outputs)")
+            .action(ArgAction::SetTrue),
+        Arg::new("format")
+            .short('f')
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output image format, picked from the --output extension if not given [default: png]")
+            .value_parser(["png", "jpeg", "webp", "avif"])
+            .num_args(1),
+        Arg::new("quality")
+            .short('q')
+            .long("quality")
+            .value_name("1-100")
+            .help("JPEG / AVIF quality [default: 90]")
+            .value_parser(clap::value_parser!(u8).range(1..=100))
+            .num_args(1),
+        Arg::new("png_compression")
+            .long("png-compression")
+            .value_name("LEVEL")
+            .help("PNG compression effort [default: default]")
+            .value_parser(["fast", "default", "best"])
+            .num_args(1),
+        Arg::new("png_indexed")
+            .long("png-indexed")
+            .help("Write a palette (indexed) PNG, a lot smaller since there are only six tiles")
+            .action(ArgAction::SetTrue),
+        Arg::new("save_grid")
+            .long("save-grid")
+            .value_name("GRID_FILE")
+            .help("Also save the dice grid, to re-render later with other faces, sizes or formats")
+            .num_args(1),
+        Arg::new("gap")
+            .long("gap")
+            .value_name("PIXELS")
+            .help("Empty space between dice [default: 0]")
+            .value_parser(clap::value_parser!(u32))
+            .num_args(1),
+        Arg::new("seed")
+            .long("seed")
+            .value_name("N|random")
+            .help("Vary the dice like a hand-built piece: other photos of a face, quarter turns, jitter and brightness, all the same for the same seed")
+            .value_parser(parse_seed)
+            .num_args(1),
+        Arg::new("jitter")
+            .long("jitter")
+            .value_name("PIXELS")
+            .help("How far a die may move each way with --seed, fractions of a pixel are fine [default: 0.3]")
+            .value_parser(clap::value_parser!(f32))
+            .num_args(1),
+        Arg::new("brightness_jitter")
+            .long("brightness-jitter")
+            .value_name("PERCENT")
+            .help("How much lighter or darker a die may get with --seed [default: 4]")
+            .value_parser(clap::value_parser!(u8).range(0..=100))
+            .num_args(1),
+        Arg::new("name_template")
+            .long("name-template")
+            .value_name("TEMPLATE")
+            .help("Batch output file names: {stem}, {name}, {index} and {ext} get filled in")
+            .default_value("{stem}_dice.{ext}")
+            .num_args(1),
+        Arg::new("watch")
+            .short('w')
+            .long("watch")
+            .help("Keep running and render again whenever the photo, the faces or the --config profile change")
+            .action(ArgAction::SetTrue),
+        Arg::new("show")
+            .long("show")
+            .value_name("PROTOCOL")
+            .help("Draw a preview of the result in the terminal (on stderr) [default protocol: auto]")
+            .value_parser(["auto", "kitty", "iterm", "sixel", "blocks"])
+            .num_args(0..=1)
+            .default_missing_value("auto"),
+        Arg::new("tune")
+            .short('t')
+            .long("tune")
+            .help("Open the tuning screen even when every setting is already given")
+            .action(ArgAction::SetTrue),
+        Arg::new("jobs")
+            .short('j')
+            .long("jobs")
+            .value_name("N")
+            .help("Photos rendered at once in batch mode [default: number of CPUs]")
+            .value_parser(clap::value_parser!(usize))
+            .num_args(1),
+    ]);
+    args
+}
+
+fn cli() -> clap::Command {
     // Using clap for argument parsing. Because why not?
-    let matches = clap::Command::new("Dice Image Processor")
+    clap::Command::new("Dice Image Processor")
         .version("1.0")
         .author("Your Name <your.email@example.com>")
         .about("Turns your images into dice art. Pretty cool, huh?")
-        .arg(
-            clap::Arg::new("input")
-                .short('i')
-                .long("input")
-                .value_name("INPUT_FILE")
-                .help("Path to the input image file")
-                .required(true)
-                .num_args(1),
+        .after_help("Without a subcommand the arguments are taken as `render` arguments.")
+        // Old scripts call it without a subcommand, so the top level still renders
+        .args(render_args())
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            clap::Command::new("render")
+                .about("Render the dice art: one image, a DeepZoom tile pyramid or a whole batch")
+                .args(render_args()),
+        )
+        .subcommand(
+            clap::Command::new("plan")
+                .about("Work out the grid and a bill of materials without rendering anything")
+                .args(input_args())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("GRID_FILE")
+                        .help("Where to write the grid, - for stdout")
+                        .default_value("-")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("bom")
+                        .long("bom")
+                        .value_name("CSV_FILE")
+                        .help("Also write the dice count per face as CSV")
+                        .num_args(1),
+                ),
         )
-        .arg(
-            clap::Arg::new("dice_dir")
-                .short('d')
-                .long("dice-dir")
-                .value_name("DICE_DIRECTORY")
-                .help("Path to the directory containing dice images (exactly 6 images)")
-                .required(true)
-                .num_args(1),
+        .subcommand(
+            clap::Command::new("stats")
+                .about("Show the tone histogram of the photo and how many of each face it takes")
+                .args(input_args()),
         )
-        .get_matches();
+        .subcommand(
+            clap::Command::new("faces")
+                .about("Check a face set and show the tone measured for each face")
+                .args(face_args())
+                .arg(
+                    Arg::new("dice_size")
+                        .short('s')
+                        .long("dice-size")
+                        .value_name("PIXELS")
+                        .help("Measure the faces scaled to this size [default: as they are]")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("config")
+                        .short('c')
+                        .long("config")
+                        .value_name("PROFILE")
+                        .help("Take the dice directory from a profile")
+                        .num_args(1),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("schema")
+                .about("Print the JSON Schema of the settings profiles, or check a profile against it")
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .value_name("PROFILE")
+                        .help("Load a TOML or JSON profile (any version) and check its settings instead")
+                        .num_args(1),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("preview")
+                .about("Quick low-res preview, in the terminal or as a small image with --output")
+                .args(input_args())
+                .args(face_args())
+                .arg(
+                    Arg::new("text")
+                        .short('t')
+                        .long("text")
+                        .value_name("STYLE")
+                        .help("How the grid is printed")
+                        .value_parser(["unicode", "digits", "ansi"])
+                        .default_value("ansi")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("max_cols")
+                        .long("max-cols")
+                        .value_name("COLUMNS")
+                        .help("Widest the preview may get, the dice size is picked to fit unless --dice-size is given")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("80")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("OUTPUT_FILE")
+                        .help("Save a small preview image (- for stdout) instead of printing")
+                        .num_args(1),
+                ),
+        )
+}
 
-    let input = matches
-        .get_one::<String>("input")
-        .expect("Input file is required")
-        .to_string();
+fn load_images_dynamic(command: Command, matches: &ArgMatches) -> Result<Images, DiceError> {
+    let input = arg::<String>(matches, "input");
 
-    let dice_dir = matches
-        .get_one::<String>("dice_dir")
-        .expect("Dice directory is required")
-        .to_string();
+    // Load the saved grid, or else the input image
+    let grid = arg::<String>(matches, "grid").map(|path| load_grid(&path)).transpose()?;
 
-    // Load the input image
-    let mut i: GrayImage = load_image(&input);
+    // A directory or a glob means a whole batch of photos, loaded one by one later
+    let batch = match &input {
+        Some(input) if command == Command::Render && grid.is_none() && is_batch_input(input) => {
+            collect_inputs(input)?
+        }
+        _ => Vec::new(),
+    };
+    if !batch.is_empty() && (flag(matches, "deep_zoom") || arg::<String>(matches, "save_grid").is_some()) {
+        return Err(DiceError::InvalidOptions(
+            "--deep-zoom and --save-grid work on one image at a time, not a batch".to_string(),
+        ));
+    }
+    let to_stdout = arg::<String>(matches, "output").is_some_and(|o| o == "-");
+    if to_stdout && command == Command::Render && (!batch.is_empty() || flag(matches, "deep_zoom")) {
+        return Err(DiceError::InvalidOptions(
+            "Batches and --deep-zoom write several files, they can't go to stdout".to_string(),
+        ));
+    }
+    if arg::<String>(matches, "show").is_some() && (!batch.is_empty() || flag(matches, "deep_zoom") || flag(matches, "stream")) {
+        return Err(DiceError::InvalidOptions(
+            "--show draws a single image, it doesn't work with batches, --deep-zoom or --stream".to_string(),
+        ));
+    }
+    let stdin_used = input.as_deref() == Some("-") || arg::<String>(matches, "grid").is_some_and(|g| g == "-");
+    if flag(matches, "watch") && (!batch.is_empty() || stdin_used || to_stdout) {
+        return Err(DiceError::InvalidOptions(
+            "--watch needs a single input file and output file, not a batch, stdin or stdout".to_string(),
+        ));
+    }
+
+    let i: GrayImage = match (&grid, &input) {
+        (None, Some(input)) if batch.is_empty() => load_input(input)?,
+        _ => GrayImage::new(0, 0), // Not needed when rendering a grid, batch photos load later
+    };
     let (original_width, original_height) = i.dimensions(); // Save the original dimensions
 
-    let mut dice = load_dice_images_d(&dice_dir);
+    // Prompt only when asked to, or when someone is actually sitting at a terminal.
+    // Never when stdin carries the image or grid, or in watch mode where it'd ask on every change.
+    let prompter = Prompter {
+        interactive: !stdin_used
+            && !flag(matches, "watch")
+            && (flag(matches, "interactive")
+                || (!flag(matches, "no_interactive") && std::io::stdin().is_terminal())),
+    };
 
-    // Ask if the user wants to invert the input image
-    println!("Invert the input image? (y/n):");
-    let mut invert_i = String::new();
-    std::io::stdin().read_line(&mut invert_i).unwrap();
-    if invert_i.trim().eq_ignore_ascii_case("y") {
-        imageops::invert(&mut i);
-        println!("Image inverted.");
-    } else {
-        println!("Keeping it original. I like it.");
+    let reuse = match arg::<String>(matches, "reuse") {
+        Some(path) => {
+            let bytes = std::fs::read(&path).map_err(|e| DiceError::io(&path, e))?;
+            let settings = read_settings(&bytes)
+                .map_err(|err| DiceError::Decode(format!("Couldn't get settings from {}: {}", path, err)))?;
+            eprintln!("Re-using settings from {} (rendered by version {})", path, settings.version);
+            Some(settings)
+        }
+        None => None,
+    };
+
+    let config = load_config(matches)?;
+
+    // Flags first, then the re-used settings, then the profile. Anything still missing gets asked (or defaulted).
+    let mut wanted = profile_from_flags(matches)
+        .or(reuse.as_ref().map(Profile::from_settings).unwrap_or_default())
+        .or(config);
+
+    // Rendering a single photo at a terminal, the tuning screen replaces the questions about
+    // dice size, preset and inversion. Questions left after it (output size, overlay) get their defaults.
+    let missing = wanted.dice_size.is_none()
+        || (wanted.preset.is_none() && wanted.thresholds.is_none())
+        || wanted.invert_input.is_none()
+        || wanted.invert_dice.is_none();
+    let can_tune = command == Command::Render
+        && prompter.interactive
+        && batch.is_empty()
+        && grid.is_none()
+        && std::io::stderr().is_terminal();
+    if flag(matches, "tune") && !can_tune {
+        return Err(DiceError::InvalidOptions(
+            "--tune needs a single photo (not a grid, batch or stdin) and a terminal to draw on".to_string(),
+        ));
+    }
+    let tuned = can_tune && (missing || flag(matches, "tune"));
+    if tuned {
+        let start = Tuning {
+            dice_size: wanted.dice_size.unwrap_or(32),
+            preset: wanted.preset()?.unwrap_or(IntensityPreset::Default),
+            invert_input: wanted.invert_input.unwrap_or(false),
+            invert_dice: wanted.invert_dice.unwrap_or(false),
+        };
+        let colours = face_colours(&dice_dir_from(&wanted)?)?;
+        let save_path = arg::<String>(matches, "save_config").unwrap_or_else(|| "dice.toml".to_string());
+        let save = |tuning: &Tuning| {
+            tuning_profile(tuning).or(wanted.clone()).save(Path::new(&save_path))?;
+            Ok(save_path.clone())
+        };
+        let tuning = tune(&i, wanted.output_size(), colours, start, &save)?;
+        wanted = tuning_profile(&tuning).or(wanted);
     }
+    let questions = Prompter { interactive: prompter.interactive && !tuned };
+
+    // From here on the answers go into `wanted` itself, so it ends up as exactly what this run uses.
+    // Previews pick their own dice size so the grid fits the terminal
+    let text = arg::<String>(matches, "text").and_then(|name| TextStyle::from_name(&name));
+    wanted.dice_size.get_or_insert_with(|| match command {
+        Command::Preview => {
+            let max_cols = arg::<u32>(matches, "max_cols").unwrap_or(80);
+            // An image preview gets one die per column
+            let style = if arg::<String>(matches, "output").is_some() { TextStyle::Digits } else { text.unwrap_or(TextStyle::Ansi) };
+            dice_size_for_columns(original_width.min(original_height), max_cols, style)
+        }
+        _ => questions.dice_size(),
+    });
 
-    // Crop the input image to a square. Dice grids love squares.
-    let square_size = original_width.min(original_height);
-    i = imageops::crop_imm(&i, 0, 0, square_size, square_size).to_image();
+    // faces.toml says which file is which face, otherwise the six images go in natural order.
+    // Found (and reported) now so a broken set fails before any questions, loaded once the options are known.
+    let face_set = if command.needs_faces() {
+        let face_set = FaceSet::find(Path::new(&dice_dir_from(&wanted)?))?;
+        eprint!("{}", face_set.report());
+        Some(face_set)
+    } else {
+        None
+    };
+
+    // Ask if the user wants to invert the input image, a grid has no image to invert
+    let invert_input = *wanted.invert_input.get_or_insert_with(|| grid.is_none() && questions.yes_no("Invert the input image?"));
+    if invert_input {
+        eprintln!("Image inverted.");
+    } else {
+        eprintln!("Keeping it original. I like it.");
+    }
 
     // Ask if the user wants to invert the dice colors
-    println!("Invert the dice colors? (y/n):");
-    let mut invert_dice_i = String::new();
-    std::io::stdin().read_line(&mut invert_dice_i).unwrap();
-    let invert_dice = invert_dice_i.trim().eq_ignore_ascii_case("y");
-
-    if invert_dice {
-        for d in &mut dice {
-            d.image.invert();
+    let invert_dice = *wanted.invert_dice.get_or_insert_with(|| command.needs_faces() && questions.yes_no("Invert the dice colors?"));
+
+    if command.needs_faces() {
+        if invert_dice {
+            eprintln!("Dice colors inverted. Edgy.");
+        } else {
+            eprintln!("Dice colors untouched. Classic.");
+        }
+    }
+
+    // Custom output size, only matters when there's an image to analyse
+    if wanted.output_size().is_none() && grid.is_none() && command != Command::Preview {
+        if let Some((width, height)) = questions.output_size() {
+            wanted.output_width = Some(width);
+            wanted.output_height = Some(height);
+        }
+    }
+
+    if wanted.preset()?.is_none() {
+        // A grid is already mapped, the preset doesn't matter
+        wanted.set_preset(if grid.is_some() { IntensityPreset::Default } else { questions.preset() });
+    }
+
+    wanted.debug_overlay.get_or_insert_with(|| {
+        command == Command::Render
+            && !flag(matches, "deep_zoom")
+            && questions.yes_no("Do you want to add debug info to output image?")
+    });
+
+    // The rest (format, gap, seed) comes as given
+    let options = RenderOptions {
+        face_size: (command == Command::Preview).then_some(PREVIEW_FACE_SIZE),
+        ..wanted.render_options()?
+    };
+
+    if flag(matches, "stream") && !matches!(options.format, OutputFormat::Png { .. }) {
+        return Err(DiceError::InvalidOptions("--stream only writes PNG, the other formats need the whole image".to_string()));
+    }
+
+    if command == Command::Render && !options.format.keeps_settings() {
+        eprintln!(
+            "Only PNG keeps the render settings, this {} can't be used with --reuse later.",
+            options.format.extension()
+        );
+    }
+
+    let variation = options.variation;
+    if variation.is_none() && (wanted.jitter.is_some() || wanted.brightness_jitter.is_some()) {
+        eprintln!("Jitter only applies with a --seed, drawing every die alike.");
+    }
+    if variation.is_some() && matches!(options.format, OutputFormat::Png { indexed: true, .. }) {
+        eprintln!("Varied dice don't fit a palette, writing a truecolour PNG instead of --png-indexed.");
+    }
+    if flag(matches, "deep_zoom") && (variation.is_some() || options.gap > 0 || options.debug) {
+        return Err(DiceError::InvalidOptions(
+            "--deep-zoom draws every die alike, edge to edge and without the overlay, it can't take --seed, --gap or --debug-overlay"
+                .to_string(),
+        ));
+    }
+
+    if let Some(path) = arg::<String>(matches, "save_config") {
+        match wanted.with_defaults()?.save(Path::new(&path)) {
+            Ok(()) => eprintln!("Settings saved to {}", path),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    let renderer = match &face_set {
+        Some(face_set) => DiceRenderer::from_face_set(options, face_set)?,
+        None => DiceRenderer::without_faces(options),
+    };
+    if let (Some(v), Some(_)) = (variation, &face_set) {
+        eprintln!("Dice varied with seed {} (jitter {}px, brightness ±{}%)", v.seed, v.jitter, v.brightness);
+    }
+    if let Some(settings) = &reuse {
+        if face_set.is_some() && settings.face_hash != renderer.face_hash() {
+            eprintln!("Heads up: these dice images aren't the ones the original was rendered with.");
         }
-        println!("Dice colors inverted. Edgy.");
-    } else {
-        println!("Dice colors untouched. Classic.");
     }
 
-    Images {
-        dice,
+    Ok(Images {
+        renderer,
+        dice_dir: wanted.dice_dir.clone(),
         input: i,
+        text,
+        deep_zoom: flag(matches, "deep_zoom"),
+        stream: flag(matches, "stream"),
+        grid,
+        save_grid: arg(matches, "save_grid"),
+        output: arg(matches, "output"),
+        batch,
+        name_template: arg(matches, "name_template").unwrap_or_default(),
+        jobs: arg(matches, "jobs").unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        }),
+        // auto only draws when there's a terminal to draw on, an explicit protocol always does
+        show: arg::<String>(matches, "show").and_then(|name| match name.as_str() {
+            "auto" if std::io::stderr().is_terminal() => Some(Graphics::detect()),
+            name => Graphics::from_name(name),
+        }),
+        prompter,
+    })
+}
+
+fn dice_dir_from(wanted: &Profile) -> Result<String, DiceError> {
+    wanted.dice_dir.clone().ok_or_else(|| {
+        DiceError::InvalidOptions(
+            "Dice directory is required (--dice-dir, or dice-dir in the --config profile)".to_string(),
+        )
+    })
+}
+
+/// What the tuning screen settled on, as a profile.
+fn tuning_profile(tuning: &Tuning) -> Profile {
+    let mut profile = Profile {
+        dice_size: Some(tuning.dice_size),
+        invert_input: Some(tuning.invert_input),
+        invert_dice: Some(tuning.invert_dice),
+        ..Profile::default()
+    };
+    profile.set_preset(tuning.preset);
+    profile
+}
+
+/// Loads the photo as grayscale, from stdin for `-`.
+fn load_input(path: &str) -> Result<GrayImage, DiceError> {
+    if path != "-" {
+        return load_image(path);
     }
+    let bytes = read_input(path).map_err(|e| DiceError::io(path, e))?;
+    // Format is guessed from the bytes, there's no extension to go by
+    Ok(image::load_from_memory(&bytes).map_err(|e| DiceError::image("stdin", e))?.into_luma8())
 }
 
-fn validate_input(i: String) -> Option<String> {
-    // Check if the file exists. If not, yell at the user.
-    if Path::new(&i).exists() {
-        Some(i)
-    } else {
-        eprintln!("File not found: {}", i);
-        println!("Press Enter to exit...");
-        let mut i = String::new();
-        std::io::stdin().read_line(&mut i).unwrap();
-        None
+fn load_grid(path: &str) -> Result<DiceGrid, DiceError> {
+    let bytes = read_input(path).map_err(|e| DiceError::io(path, e))?;
+    DiceGrid::from_text(&String::from_utf8_lossy(&bytes))
+}
+
+fn load_config(matches: &ArgMatches) -> Result<Profile, DiceError> {
+    match arg::<String>(matches, "config") {
+        Some(path) => Profile::load(Path::new(&path)),
+        None => Ok(Profile::default()),
+    }
+}
+
+/// Only the settings actually given on the command line, everything else left as None.
+fn profile_from_flags(matches: &ArgMatches) -> Profile {
+    let output_size = arg::<(u32, u32)>(matches, "output_size");
+
+    // An explicit --format wins, otherwise go by the --output extension
+    let format = arg::<String>(matches, "format").or_else(|| {
+        let output = arg::<String>(matches, "output")?;
+        let ext = Path::new(&output).extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "jpg" | "jpeg" => "jpeg".to_string(),
+            "webp" => "webp".to_string(),
+            "avif" => "avif".to_string(),
+            _ => "png".to_string(),
+        })
+    });
+
+    Profile {
+        version: None,
+        dice_dir: arg(matches, "dice_dir"),
+        dice_size: arg(matches, "dice_size"),
+        preset: arg(matches, "preset"),
+        thresholds: arg(matches, "thresholds"),
+        invert_input: arg(matches, "invert_input"),
+        invert_dice: arg(matches, "invert_dice"),
+        output_width: output_size.map(|(w, _)| w),
+        output_height: output_size.map(|(_, h)| h),
+        gap: arg(matches, "gap"),
+        debug_overlay: arg(matches, "debug_overlay"),
+        format,
+        quality: arg(matches, "quality"),
+        png_compression: arg(matches, "png_compression"),
+        png_indexed: flag(matches, "png_indexed").then_some(true),
+        seed: arg(matches, "seed"),
+        jitter: arg(matches, "jitter"),
+        brightness_jitter: arg(matches, "brightness_jitter"),
     }
 }
 
 fn main() {
-    // Load the dice and input image
-    let mut dicks: Images = load_images_dynamic();
-    if dicks.dice.is_empty() || dicks.dice[0].image.width() == 0 || dicks.dice[0].image.height() == 0 {
-        eprintln!("Dice images are missing or invalid. Fix it.");
-        return;
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        std::process::exit(err.exit_code());
     }
+}
 
-    let dw = dicks.dice[0].image.width();
-    let dh = dicks.dice[0].image.height();
+fn run() -> Result<(), DiceError> {
+    let matches = cli().get_matches();
+    let (name, sub) = matches.subcommand().unwrap_or(("render", &matches));
+    if let Some(threads) = arg::<u32>(sub, "threads") {
+        // Only fails if the pool is already running, and nothing has used it yet
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(threads as usize).build_global();
+    }
+    let command = match name {
+        "faces" => return check_faces(sub),
+        "schema" => return print_schema(sub),
+        "plan" => Command::Plan,
+        "stats" => Command::Stats,
+        "preview" => Command::Preview,
+        _ => Command::Render,
+    };
 
-    // Resize the input image if needed
-    match resize_output(&dicks.input) {
-        Some(resized_input) => {
-            dicks.input = resized_input;
+    // Load the dice and input image
+    let mut dicks: Images = load_images_dynamic(command, sub)?;
+
+    match command {
+        Command::Render if flag(sub, "watch") => watch(dicks, sub),
+        Command::Render => render(&mut dicks),
+        Command::Plan => {
+            let grid = make_grid(&mut dicks)?;
+            plan(&grid, &dicks, arg::<String>(sub, "bom"))
+        }
+        Command::Stats => stats(dicks),
+        Command::Preview => {
+            let grid = make_grid(&mut dicks)?;
+            preview(&grid, &dicks)
         }
-        None => {}
     }
+}
 
-    // Recalculate grid dimensions based on the resized input image
-    let (iwidth, iheight) = dicks.input.dimensions();
-    let num_dice_x = iwidth / dw;
-    let num_dice_y = iheight / dh;
-
-    // Prepare the output image
-    let mut ow = num_dice_x * dw;
-    let mut oh = num_dice_y * dh;
-    let mut oi = RgbaImage::new(ow, oh);
-
-    // Ask the user for an intensity preset
-    println!("Pick your intensity preset:");
-    println!("1. Default");
-    println!("2. High Contrast");
-    println!("3. Low Contrast");
-    println!("4. Bright");
-    println!("5. Dark");
-
-    let mut preset_input = String::new();
-    std::io::stdin().read_line(&mut preset_input).unwrap();
-    let preset = match preset_input.trim() {
-        "1" => IntensityPreset::Default,
-        "2" => IntensityPreset::HighContrast,
-        "3" => IntensityPreset::LowContrast,
-        "4" => IntensityPreset::Bright,
-        "5" => IntensityPreset::Dark,
-        _ => {
-            println!("Invalid choice. Defaulting to Default preset.");
-            IntensityPreset::Default
+/// The saved grid if there is one, otherwise the grid worked out from the input photo.
+fn make_grid(dicks: &mut Images) -> Result<DiceGrid, DiceError> {
+    match dicks.grid.take() {
+        Some(grid) => {
+            eprintln!("Using saved grid of {}x{} dice", grid.width, grid.height);
+            Ok(grid)
+        }
+        None => {
+            let input = std::mem::replace(&mut dicks.input, GrayImage::new(0, 0));
+            dicks.renderer.analyze(&dicks.renderer.preprocess(input), &mut progress_bar())
         }
+    }
+}
+
+fn render(dicks: &mut Images) -> Result<(), DiceError> {
+    if !dicks.batch.is_empty() {
+        let result = render_batch(dicks);
+        dicks.prompter.wait_for_exit();
+        return result;
+    }
+
+    let (dw, dh) = dicks.renderer.die_size();
+    let (iwidth, iheight) = dicks.input.dimensions();
+
+    // A saved grid skips the analysis, only the faces, size, gap and format change
+    let from_grid = dicks.grid.is_some();
+    let grid = make_grid(dicks)?;
+
+    if let Some(path) = &dicks.save_grid {
+        std::fs::write(path, grid.to_text()).map_err(|e| DiceError::io(path, e))?;
+        eprintln!("Grid saved to {}", path);
+    }
+
+    let total_dice = grid.width * grid.height;
+    let output_path = dicks
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("output/dice_output.{}", dicks.renderer.options.format.extension()));
+
+    // Huge outputs go out as tiles, the full image never exists in memory
+    if dicks.deep_zoom {
+        let output = Path::new(&output_path);
+        let out_dir = output.parent().unwrap_or(Path::new("."));
+        let name = output.file_stem().and_then(|n| n.to_str()).unwrap_or("dice_output");
+        std::fs::create_dir_all(out_dir).map_err(|e| DiceError::io(out_dir, e))?;
+        let descriptor = write_deep_zoom(&grid, dicks.renderer.dice(), (dw, dh), out_dir, name)?;
+        eprintln!("Total dice used: {}", total_dice);
+        eprintln!("Output image size: {}x{}", grid.width * dw, grid.height * dh);
+        eprintln!("Tile pyramid saved to {}", descriptor.display());
+        return Ok(());
+    }
+
+    // Big PNGs go out a row of dice at a time, the full image never exists in memory
+    let (ow, oh) = dicks.renderer.output_size(&grid)?;
+    let streamed = matches!(dicks.renderer.options.format, OutputFormat::Png { .. })
+        && dicks.show.is_none()
+        && (dicks.stream || ow as u64 * oh as u64 * 4 > STREAM_ABOVE);
+
+    let oi = if streamed {
+        create_parent_dir(&output_path)?;
+        let mut out = create_output(&output_path).map_err(|e| DiceError::io(&output_path, e))?;
+        dicks.renderer.write_png(&grid, from_grid, &mut out, &mut progress_bar())?;
+        out.flush().map_err(|e| DiceError::io(&output_path, e))?;
+        eprintln!("Streamed the PNG a row of dice at a time");
+        None
+    } else {
+        // Construct the output, then save it
+        let mut hooks = progress_bar();
+        let oi = dicks.renderer.render(&grid, &mut hooks)?;
+        create_parent_dir(&output_path)?;
+        hooks.report(Stage::Encoding, 0, 1)?;
+        let bytes = dicks.renderer.encode(&grid, &oi, from_grid)?;
+        hooks.report(Stage::Encoding, 1, 1)?;
+        write_output(&output_path, &bytes).map_err(|e| DiceError::io(&output_path, e))?;
+        Some(oi)
     };
+    if dicks.renderer.options.debug {
+        eprintln!("Debug info added to image");
+    } else {
+        eprintln!("No debug info added.");
+    }
+
+    if iwidth > 0 {
+        eprintln!("Original image size: {}x{}", iwidth, iheight);
+    }
+    eprintln!("Dice size used: {}x{}", dw, dh);
+    eprintln!("Total dice used: {}", total_dice);
+    eprintln!("Output image size: {}x{}", ow, oh);
+    if output_path == "-" {
+        eprintln!("Output written to stdout");
+    } else {
+        eprintln!("Output saved to {}", output_path);
+    }
+    if let (Some(graphics), Some(oi)) = (dicks.show, &oi) {
+        // stderr, so it still works when the image itself goes to stdout
+        let mut stderr = std::io::stderr().lock();
+        stderr.write_all(&show_image(oi, graphics)).and_then(|()| stderr.flush()).map_err(|e| DiceError::io("-", e))?;
+    }
+
+    // Keep the window open
+    dicks.prompter.wait_for_exit();
+    Ok(())
+}
+
+/// Renders, then keeps rendering again whenever something it was made from changes.
+/// If only the photo (or grid file) changed the faces already loaded are reused.
+/// A failed render or reload is reported and the watch carries on, the next save probably fixes it.
+fn watch(mut dicks: Images, matches: &ArgMatches) -> Result<(), DiceError> {
+    let source_path = arg::<String>(matches, "grid").or_else(|| arg(matches, "input")).unwrap();
+    let from_grid = arg::<String>(matches, "grid").is_some();
+    let mut source = Watched::new(&source_path);
+    let mut faces = Watched::new(dicks.dice_dir.clone().unwrap_or_default());
+    let mut config = arg::<String>(matches, "config").map(Watched::new);
 
-    // Map blocks to dice and construct the output
-    for grid_y in 0..num_dice_y {
-        for grid_x in 0..num_dice_x {
-            let block_start_x = grid_x * dw;
-            let block_start_y = grid_y * dh;
+    loop {
+        // make_grid takes the input, keep a copy for when the faces change but the photo doesn't
+        let input = dicks.input.clone();
+        let grid = dicks.grid.clone();
+        if let Err(err) = render(&mut dicks) {
+            eprintln!("Error: {}", err);
+        }
+        dicks.input = input;
+        dicks.grid = grid;
+
+        eprintln!();
+        let mut watched = vec![source.path().display().to_string(), faces.path().display().to_string()];
+        watched.extend(config.as_ref().map(|c| c.path().display().to_string()));
+        eprintln!("Watching {} for changes (Ctrl+C to stop)", watched.join(", "));
 
-            let block_view = imageops::crop_imm(&dicks.input, block_start_x, block_start_y, dw, dh);
+        let (mut photo_changed, mut settings_changed) = (false, false);
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            // Not ||, every watcher has to update its stamp
+            let photo = source.changed();
+            let settings = faces.changed() | config.as_mut().is_some_and(|c| c.changed());
+            photo_changed |= photo;
+            settings_changed |= settings;
+            // Wait for one quiet poll so half-written files don't get read
+            if (photo_changed || settings_changed) && !photo && !settings {
+                break;
+            }
+        }
 
-            let mut total_intensity: u64 = 0;
-            let num_pixels_in_block = (dw * dh) as u64;
-            for pixel in block_view.to_image().pixels() {
-                total_intensity += pixel[0] as u64;
+        if settings_changed {
+            eprintln!("Faces or profile changed, reloading everything");
+            match load_images_dynamic(Command::Render, matches) {
+                Ok(reloaded) => dicks = reloaded,
+                Err(err) => eprintln!("Error: {} (still using the old settings)", err),
             }
-            let avg_intensity = if num_pixels_in_block > 0 {
-                (total_intensity / num_pixels_in_block) as u8
+        } else if photo_changed {
+            eprintln!("{} changed, re-rendering with the same faces", source_path);
+            let reloaded = if from_grid {
+                load_grid(&source_path).map(|grid| dicks.grid = Some(grid))
             } else {
-                0
+                load_input(&source_path).map(|input| dicks.input = input)
             };
+            if let Err(err) = reloaded {
+                eprintln!("Error: {} (still using the old one)", err);
+            }
+        }
+    }
+}
 
-            let target_side: DiceSides = map_intensity_to_dice_side(avg_intensity, &preset);
+/// Writes the grid and the bill of materials, nothing gets rendered.
+fn plan(grid: &DiceGrid, dicks: &Images, bom: Option<String>) -> Result<(), DiceError> {
+    let output = dicks.output.clone().unwrap_or_else(|| "-".to_string());
+    write_output(&output, grid.to_text().as_bytes()).map_err(|e| DiceError::io(&output, e))?;
+    if output != "-" {
+        eprintln!("Grid saved to {}", output);
+    }
 
-            let dice_to_draw = dicks.dice.iter().find(|&d| d.side == target_side);
+    eprint!("{}", usage_table(grid));
+    if let Some(path) = bom {
+        std::fs::write(&path, bom_csv(grid)).map_err(|e| DiceError::io(&path, e))?;
+        eprintln!("Bill of materials saved to {}", path);
+    }
+    Ok(())
+}
 
-            if let Some(dice) = dice_to_draw {
-                let paste_x = grid_x * dw;
-                let paste_y = grid_y * dh;
-                let dice_rgba = dice.image.to_rgba8();
-                imageops::overlay(&mut oi, &dice_rgba, paste_x as i64, paste_y as i64);
-            } else {
-                eprintln!(
-                    "Warning: Could not find dice for side {:?} at grid ({}, {})",
-                    target_side, grid_x, grid_y
+/// Histogram of the prepared photo and face usage, printed to stdout.
+fn stats(mut dicks: Images) -> Result<(), DiceError> {
+    if dicks.grid.is_none() {
+        let input = std::mem::replace(&mut dicks.input, GrayImage::new(0, 0));
+        dicks.input = dicks.renderer.preprocess(input);
+        println!("{}", histogram(&dicks.input, &dicks.renderer.options.preset));
+        dicks.grid = Some(dicks.renderer.analyze(&dicks.input, &mut Hooks::none())?);
+    }
+    print!("{}", usage_table(dicks.grid.as_ref().unwrap()));
+    Ok(())
+}
+
+/// Prints the grid, or saves a small image of it with --output.
+fn preview(grid: &DiceGrid, dicks: &Images) -> Result<(), DiceError> {
+    match &dicks.output {
+        Some(path) => {
+            let oi = render_grid(grid, dicks.renderer.dice(), 0)?;
+            let bytes = encode_image(&oi, &dicks.renderer.options.format, None)?;
+            create_parent_dir(path)?;
+            write_output(path, &bytes).map_err(|e| DiceError::io(path, e))?;
+            eprintln!("Preview ({}x{}) saved to {}", oi.width(), oi.height(), path);
+        }
+        None => print!("{}", render_text(grid, dicks.renderer.dice(), dicks.text.unwrap_or(TextStyle::Ansi))),
+    }
+    Ok(())
+}
+
+/// Prints the JSON Schema for profiles, or with --check loads one and says whether it'd render.
+fn print_schema(matches: &ArgMatches) -> Result<(), DiceError> {
+    match arg::<String>(matches, "check") {
+        Some(path) => {
+            Profile::load(Path::new(&path))?.render_options()?;
+            println!("{}: fine for version {}", path, profile::VERSION);
+        }
+        None => println!("{}", Profile::json_schema()),
+    }
+    Ok(())
+}
+
+/// Loads every face in the dice directory and reports what it finds. An error if the set is unusable.
+fn check_faces(matches: &ArgMatches) -> Result<(), DiceError> {
+    let wanted = profile_from_flags(matches).or(load_config(matches)?);
+    let dice_dir = wanted.dice_dir.ok_or_else(|| {
+        DiceError::InvalidOptions(
+            "Dice directory is required (--dice-dir, or dice-dir in the --config profile)".to_string(),
+        )
+    })?;
+
+    let face_set = FaceSet::find(Path::new(&dice_dir))?;
+    print!("{}", face_set.report());
+    println!();
+
+    let mut broken = Vec::new();
+    let mut tones = Vec::new();
+    let mut sizes = Vec::new();
+
+    println!("Face  File                          Size        Tone  Expected");
+    for face in &face_set.faces {
+        let name = face.path.file_name().and_then(|n| n.to_str()).unwrap_or("?");
+        match image::open(&face.path) {
+            Ok(mut image) => {
+                if let Some(size) = wanted.dice_size {
+                    image = image.resize_exact(size, size, imageops::FilterType::Lanczos3);
+                }
+                if wanted.invert_dice.unwrap_or(false) {
+                    image.invert();
+                }
+                let tone = face_tone(&image);
+                let expected = face.tone.map(|t| t.to_string()).unwrap_or_default();
+                println!(
+                    "{:<5} {:<29} {:<11} {:>4}  {:>8}",
+                    face.id,
+                    name,
+                    format!("{}x{}", image.width(), image.height()),
+                    tone,
+                    expected
                 );
+                if face.tone.is_some_and(|t| t.abs_diff(tone) > 16) {
+                    println!("      Warning: measured tone is a long way off the manifest's");
+                }
+                tones.push(tone);
+                sizes.push((image.width(), image.height()));
+            }
+            Err(err) => {
+                println!("{:<5} {:<29} {}", face.id, name, err);
+                broken.push(face.id.to_string());
             }
         }
     }
 
-    println!("Do you want to add debug info to output image? (y/n):");
-    let mut invert_i = String::new();
-    std::io::stdin().read_line(&mut invert_i).unwrap();
-    if invert_i.trim().eq_ignore_ascii_case("y") {
-        add_reference_text(
-            &mut oi,
-            (dw, dh),
-            num_dice_x * num_dice_y,
-            (ow, oh),
-        );
-        println!("Debug info added to image");
+    println!();
+    if sizes.iter().any(|&(w, h)| w != h) {
+        println!("Warning: not every face is square, they get stretched to the dice size");
+    }
+    if sizes.windows(2).any(|w| w[0] != w[1]) {
+        println!("Warning: the faces aren't all the same size");
+    }
+    // Face One stands for the darkest blocks of the photo, Six for the brightest
+    if tones.windows(2).all(|w| w[0] < w[1]) {
+        println!("Faces get brighter from One to Six, the photo comes out as it is.");
+    } else if tones.windows(2).all(|w| w[0] > w[1]) {
+        println!("Faces get darker from One to Six, the piece comes out as a negative unless --invert-input or --invert-dice.");
     } else {
-        println!("No debug info added.");
+        println!("Warning: the tones don't go steadily from dark to light, parts of the photo will come out mixed up");
     }
-
-    // Save the output image
-    let output_path = "output/dice_output.png";
-    if let Some(parent_dir) = Path::new(output_path).parent() {
-        std::fs::create_dir_all(parent_dir).expect("Failed to create output directory");
+    if !broken.is_empty() {
+        return Err(DiceError::InvalidFaceSet(format!("couldn't load face {}", broken.join(", "))));
     }
-    oi.save(output_path).unwrap_or_else(|err| {
-        eprintln!("Error saving output image: {}", err);
-    });
-    println!("Original image size: {}x{}", iwidth, iheight);
-    println!("Dice size used: {}x{}", dw, dh);
-    println!("Total dice used: {}", num_dice_x * num_dice_y);
-    println!("Output image size: {}x{}", ow, oh);
-    println!("Output saved to {}", output_path);
+    println!("Face set looks usable.");
+    Ok(())
+}
 
-    // Keep the window open
-    println!("Press Enter to exit...");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-}
-
-fn resize_output(input: &GrayImage) -> Option<GrayImage> {
-    println!("Do you want to set a custom output image size? (y/n):");
-    let mut custom_size_input = String::new();
-    std::io::stdin().read_line(&mut custom_size_input).unwrap();
-    if custom_size_input.trim().eq_ignore_ascii_case("y") {
-        println!("Enter the desired output image width (e.g., 1920 for desktop wallpaper):");
-        let mut output_width_input = String::new();
-        std::io::stdin().read_line(&mut output_width_input).unwrap();
-        let output_width: u32 = match output_width_input.trim().parse() {
-            Ok(width) if width > 0 => width,
-            _ => {
-                println!("Invalid width. Using default width of 1920.");
-                1920
-            }
-        };
+/// Renders every photo of the batch with the same faces and settings, then prints the summary.
+/// Fails with the first failed photo's error, so the exit code says what went wrong.
+fn render_batch(dicks: &Images) -> Result<(), DiceError> {
+    let out_dir = Path::new(dicks.output.as_deref().unwrap_or("output"));
+    let extension = dicks.renderer.options.format.extension();
+    // Before anything is written, a clash would otherwise only show as a missing output
+    let names = output_names(&dicks.name_template, &dicks.batch, extension)?;
+    std::fs::create_dir_all(out_dir).map_err(|e| DiceError::io(out_dir, e))?;
+    eprintln!("Rendering {} photos ({} at a time) into {}", dicks.batch.len(), dicks.jobs, out_dir.display());
 
-        println!("Enter the desired output image height (e.g., 1080 for desktop wallpaper):");
-        let mut output_height_input = String::new();
-        std::io::stdin().read_line(&mut output_height_input).unwrap();
-        let output_height: u32 = match output_height_input.trim().parse() {
-            Ok(height) if height > 0 => height,
-            _ => {
-                println!("Invalid height. Using default height of 1080.");
-                1080
-            }
-        };
+    let renderer = &dicks.renderer;
+    let results = run_batch(&dicks.batch, dicks.jobs, |index, input| {
+        let photo = image::open(input).map_err(|e| DiceError::image(input, e))?.into_luma8();
+        // Several photos at once, a bar each would just fight over the line
+        let grid = renderer.analyze(&renderer.preprocess(photo), &mut Hooks::none())?;
+        let oi = renderer.render(&grid, &mut Hooks::none())?;
+        let bytes = renderer.encode(&grid, &oi, false)?;
+        let output = out_dir.join(&names[index]);
+        std::fs::write(&output, bytes).map_err(|e| DiceError::io(&output, e))?;
 
-        println!("Custom output size set to {}x{}", output_width, output_height);
+        Ok(JobOutcome {
+            output,
+            grid: (grid.width, grid.height),
+            output_size: oi.dimensions(),
+        })
+    });
 
-        // Create a new blank image with the desired dimensions
-        let mut resized_input = GrayImage::new(output_width, output_height);
+    print_summary(&results);
+    match results.into_iter().find_map(|r| r.outcome.err()) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
 
-        // Calculate the aspect ratio of the original image
-        let (input_width, input_height) = input.dimensions();
-        let aspect_ratio = input_width as f32 / input_height as f32;
+fn create_parent_dir(path: &str) -> Result<(), DiceError> {
+    match Path::new(path).parent() {
+        Some(parent_dir) => std::fs::create_dir_all(parent_dir).map_err(|e| DiceError::io(parent_dir, e)),
+        None => Ok(()),
+    }
+}
 
-        // Calculate the new dimensions for the original image while maintaining aspect ratio
-        let (new_width, new_height) = if output_width as f32 / output_height as f32 > aspect_ratio {
-            // Constrain by height
-            let new_width = (output_height as f32 * aspect_ratio).round() as u32;
-            (new_width, output_height)
-        } else {
-            // Constrain by width
-            let new_height = (output_width as f32 / aspect_ratio).round() as u32;
-            (output_width, new_height)
-        };
+/// Reads a whole file, or all of stdin for `-`.
+fn read_input(path: &str) -> std::io::Result<Vec<u8>> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        std::fs::read(path)
+    }
+}
 
-        // Resize the original image to the new dimensions
-        let scaled_input = image::imageops::resize(
-            input,
-            new_width,
-            new_height,
-            imageops::FilterType::Lanczos3, // High-quality resizing filter
-        );
+/// A file to write a bit at a time, or stdout for `-`.
+fn create_output(path: &str) -> std::io::Result<Box<dyn Write>> {
+    if path == "-" {
+        Ok(Box::new(std::io::stdout().lock()))
+    } else {
+        Ok(Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)))
+    }
+}
 
-        // Calculate the offsets to center the scaled image
-        let offset_x = ((output_width - new_width) / 2) as i64;
-        let offset_y = ((output_height - new_height) / 2) as i64;
+/// Writes a whole file, or to stdout for `-`.
+fn write_output(path: &str, bytes: &[u8]) -> std::io::Result<()> {
+    if path == "-" {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()
+    } else {
+        std::fs::write(path, bytes)
+    }
+}
 
-        // Overlay the scaled image onto the new blank image
-        imageops::overlay(&mut resized_input, &scaled_input, offset_x, offset_y);
+/// A seed for --seed, `random` picks one (it's printed and saved in the output, so it can be used again).
+fn parse_seed(value: &str) -> Result<u32, String> {
+    if value != "random" {
+        return value.parse().map_err(|_| format!("expected a number or random, got {}", value));
+    }
+    // The std hasher is keyed randomly for every process, that's all the randomness it takes
+    Ok(std::collections::hash_map::RandomState::new().hash_one(std::time::SystemTime::now()) as u32)
+}
 
-        Some(resized_input)
-    } else {
-        None
+/// Parses WIDTHxHEIGHT, e.g. 1920x1080.
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (w, h) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {}", value))?;
+    match (w.trim().parse::<u32>(), h.trim().parse::<u32>()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("expected WIDTHxHEIGHT, got {}", value)),
     }
 }
@@ -0,0 +1,234 @@
+use std::io::Cursor;
+
+use crate::error::DiceError;
+use crate::variation::Variation;
+
+/// Keyword of the PNG tEXt chunk the settings are stored under.
+pub const METADATA_KEY: &str = "dice-settings";
+
+/// Everything needed to render the same mosaic again, embedded in every PNG we write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub version: String, // crate version that rendered it
+    pub preset: String,  // IntensityPreset name, e.g. "HighContrast"
+    pub dice_size: u32,
+    pub invert_input: bool,
+    pub invert_dice: bool,
+    pub output_size: Option<(u32, u32)>, // custom canvas, None = input size
+    pub grid: (u32, u32),                // dice across, dice down
+    pub gap: u32,                        // empty pixels between dice
+    pub face_hash: String,               // FNV-1a of the six face files, in order
+    pub debug: bool,
+    pub thresholds: Option<[u8; 5]>, // only for the Custom preset
+    pub variation: Option<Variation>, // seed and amounts, when the dice were varied
+}
+
+impl RenderSettings {
+    /// Plain `key=value` lines, readable with any PNG metadata tool.
+    pub fn to_text(&self) -> String {
+        let output_size = match self.output_size {
+            Some((w, h)) => format!("{}x{}", w, h),
+            None => "none".to_string(),
+        };
+        format!(
+            "version={}\npreset={}\ndice_size={}\ninvert_input={}\ninvert_dice={}\noutput_size={}\ngrid={}x{}\ngap={}\nface_hash={}\ndebug={}\n",
+            self.version,
+            self.preset,
+            self.dice_size,
+            self.invert_input,
+            self.invert_dice,
+            output_size,
+            self.grid.0,
+            self.grid.1,
+            self.gap,
+            self.face_hash,
+            self.debug,
+        ) + &match self.thresholds {
+            Some(t) => format!("thresholds={},{},{},{},{}\n", t[0], t[1], t[2], t[3], t[4]),
+            None => String::new(),
+        } + &match self.variation {
+            Some(v) => format!("seed={}\njitter={}\nbrightness_jitter={}\n", v.seed, v.jitter, v.brightness),
+            None => String::new(),
+        }
+    }
+
+    pub fn from_text(text: &str) -> Result<RenderSettings, DiceError> {
+        let mut settings = RenderSettings {
+            version: String::new(),
+            preset: "Default".to_string(),
+            dice_size: 32,
+            invert_input: false,
+            invert_dice: false,
+            output_size: None,
+            grid: (0, 0),
+            gap: 0,
+            face_hash: String::new(),
+            debug: false,
+            thresholds: None,
+            variation: None,
+        };
+        // Only a seed turns variation on, the amounts may come before or after it
+        let (mut seed, mut variation) = (None, Variation::default());
+
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| DiceError::Decode(format!("Malformed settings line: {}", line)))?;
+            let bad = || DiceError::Decode(format!("Bad value for {}: {}", key, value));
+            match key {
+                "version" => settings.version = value.to_string(),
+                "preset" => settings.preset = value.to_string(),
+                "dice_size" => settings.dice_size = value.parse().map_err(|_| bad())?,
+                "invert_input" => settings.invert_input = value.parse().map_err(|_| bad())?,
+                "invert_dice" => settings.invert_dice = value.parse().map_err(|_| bad())?,
+                "output_size" if value == "none" => settings.output_size = None,
+                "output_size" => settings.output_size = Some(parse_size(value).ok_or_else(bad)?),
+                "grid" => settings.grid = parse_size(value).ok_or_else(bad)?,
+                "gap" => settings.gap = value.parse().map_err(|_| bad())?,
+                "face_hash" => settings.face_hash = value.to_string(),
+                "debug" => settings.debug = value.parse().map_err(|_| bad())?,
+                "thresholds" => {
+                    let t: Vec<u8> = value.split(',').map(|v| v.parse().map_err(|_| bad())).collect::<Result<_, _>>()?;
+                    settings.thresholds = Some(t.try_into().map_err(|_| bad())?);
+                }
+                "seed" => seed = Some(value.parse().map_err(|_| bad())?),
+                "jitter" => variation.jitter = value.parse().map_err(|_| bad())?,
+                "brightness_jitter" => variation.brightness = value.parse().map_err(|_| bad())?,
+                _ => {} // Written by a newer version, ignore
+            }
+        }
+        settings.variation = seed.map(|seed| Variation { seed, ..variation });
+
+        Ok(settings)
+    }
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (w, h) = value.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+/// Pulls the render settings back out of a PNG we generated.
+pub fn read_settings(png_bytes: &[u8]) -> Result<RenderSettings, DiceError> {
+    let decoder = png::Decoder::new(Cursor::new(png_bytes));
+    let reader = decoder.read_info().map_err(|e| DiceError::Decode(e.to_string()))?;
+    let chunk = reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .find(|c| c.keyword == METADATA_KEY)
+        .ok_or_else(|| DiceError::Decode("No dice settings found in this PNG".to_string()))?;
+    RenderSettings::from_text(&chunk.text)
+}
+
+/// Running FNV-1a 64 hash, good enough to tell face sets apart.
+pub struct FaceHasher(u64);
+
+impl Default for FaceHasher {
+    fn default() -> Self {
+        FaceHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl FaceHasher {
+    pub fn new() -> Self {
+        FaceHasher::default()
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dicelib::IntensityPreset;
+    use crate::profile::Profile;
+    use crate::progress::Hooks;
+    use crate::renderer::{DiceRenderer, RenderOptions};
+    use crate::tileset::ProceduralDice;
+
+    /// A 48x48 diagonal gradient as PNG bytes, enough tones for every face.
+    fn photo() -> Vec<u8> {
+        let image = image::GrayImage::from_fn(48, 48, |x, y| image::Luma([((x + y) * 255 / 94) as u8]));
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let settings = RenderSettings {
+            version: "0.1.0".to_string(),
+            preset: "Custom".to_string(),
+            dice_size: 12,
+            invert_input: true,
+            invert_dice: false,
+            output_size: Some((640, 480)),
+            grid: (53, 40),
+            gap: 2,
+            face_hash: "0123456789abcdef".to_string(),
+            debug: true,
+            thresholds: Some([10, 20, 30, 40, 50]),
+            variation: Some(Variation { seed: 7, jitter: 0.5, brightness: 9 }),
+        };
+        assert_eq!(RenderSettings::from_text(&settings.to_text()).unwrap(), settings);
+    }
+
+    #[test]
+    fn unknown_keys_are_skipped_and_bad_values_rejected() {
+        let settings = RenderSettings::from_text("dice_size=8\nsomething_new=1\n").unwrap();
+        assert_eq!(settings.dice_size, 8);
+        assert!(matches!(RenderSettings::from_text("dice_size=big"), Err(DiceError::Decode(_))));
+        assert!(matches!(RenderSettings::from_text("no equals sign"), Err(DiceError::Decode(_))));
+    }
+
+    #[test]
+    fn reuse_renders_the_same_mosaic() {
+        // Faces drawn bigger than the photo pixels per die, the settings have to keep the latter
+        let options = RenderOptions {
+            dice_size: 4,
+            face_size: Some(6),
+            preset: IntensityPreset::Custom([30, 70, 120, 170, 220]),
+            gap: 1,
+            variation: Some(Variation { seed: 3, ..Variation::default() }),
+            ..RenderOptions::default()
+        };
+        let renderer = DiceRenderer::from_tile_set(options.clone(), &ProceduralDice::default()).unwrap();
+        let first = renderer.process(&photo(), &mut Hooks::none()).unwrap();
+
+        let settings = read_settings(&first).unwrap();
+        assert_eq!(settings.dice_size, 4);
+        assert_eq!(settings.grid, (12, 12));
+        let reused = Profile::from_settings(&settings).render_options().unwrap();
+        assert_eq!(reused.dice_size, options.dice_size);
+        assert_eq!(reused.preset, options.preset);
+        assert_eq!(reused.gap, options.gap);
+        assert_eq!(reused.variation, options.variation);
+
+        let again = RenderOptions { face_size: options.face_size, ..reused };
+        let renderer = DiceRenderer::from_tile_set(again, &ProceduralDice::default()).unwrap();
+        assert_eq!(renderer.process(&photo(), &mut Hooks::none()).unwrap(), first);
+    }
+
+    #[test]
+    fn only_png_keeps_settings() {
+        let options = RenderOptions {
+            dice_size: 8,
+            format: crate::encode::OutputFormat::WebP,
+            ..RenderOptions::default()
+        };
+        let renderer = DiceRenderer::from_tile_set(options, &ProceduralDice::default()).unwrap();
+        let webp = renderer.process(&photo(), &mut Hooks::none()).unwrap();
+        assert!(!renderer.options.format.keeps_settings());
+        assert!(read_settings(&webp).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! Data-parallel loops over rows of dice. With the `parallel` feature the rows are spread over
+//! rayon's thread pool (the CLI sizes it with --threads), without it they're plain loops, e.g. in
+//! the browser build. Results always come back in row order, so the output is byte-identical.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How many rows are worth having in flight at once.
+pub fn threads() -> usize {
+    #[cfg(feature = "parallel")]
+    return rayon::current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    return 1;
+}
+
+/// `f(0)`, `f(1)`, ... `f(n - 1)`, in that order.
+pub fn map_rows<T: Send>(rows: std::ops::Range<u32>, f: impl Fn(u32) -> T + Sync + Send) -> Vec<T> {
+    #[cfg(feature = "parallel")]
+    return rows.into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return rows.map(f).collect();
+}
+
+/// `f(index, chunk)` for every `size` bytes of `data`, the last chunk may be shorter.
+pub fn for_each_chunk(data: &mut [u8], size: usize, f: impl Fn(usize, &mut [u8]) + Sync + Send) {
+    #[cfg(feature = "parallel")]
+    data.par_chunks_mut(size).enumerate().for_each(|(i, chunk)| f(i, chunk));
+    #[cfg(not(feature = "parallel"))]
+    data.chunks_mut(size).enumerate().for_each(|(i, chunk)| f(i, chunk));
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use crate::progress::Hooks;
+    use crate::renderer::{DiceRenderer, RenderOptions};
+    use crate::tileset::ProceduralDice;
+    use crate::variation::Variation;
+
+    fn render_on(threads: usize, photo: &[u8]) -> Vec<u8> {
+        let options = RenderOptions {
+            dice_size: 12,
+            gap: 1,
+            debug: true,
+            variation: Some(Variation { seed: 42, ..Variation::default() }),
+            ..RenderOptions::default()
+        };
+        let renderer = DiceRenderer::from_tile_set(options, &ProceduralDice::default()).unwrap();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| renderer.process(photo, &mut Hooks::none()).unwrap())
+    }
+
+    #[test]
+    fn same_bytes_on_one_thread_and_many() {
+        // The demo photo, shrunk so the debug build gets through it quickly
+        let mut photo = Vec::new();
+        image::open(concat!(env!("CARGO_MANIFEST_DIR"), "/../../images/image.png"))
+            .unwrap()
+            .thumbnail(300, 300)
+            .write_to(&mut std::io::Cursor::new(&mut photo), image::ImageFormat::Png)
+            .unwrap();
+        let sequential = render_on(1, &photo);
+        assert_eq!(render_on(4, &photo), sequential);
+        assert_eq!(render_on(7, &photo), sequential);
+    }
+}
@@ -0,0 +1,484 @@
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::dicelib::IntensityPreset;
+use crate::encode::{OutputFormat, PngCompression};
+use crate::error::DiceError;
+use crate::metadata::RenderSettings;
+use crate::renderer::RenderOptions;
+use crate::variation::Variation;
+
+/// Layout version this build writes. Older profiles are brought up to it by `migrate` when loaded.
+pub const VERSION: u32 = 1;
+
+/// Render settings saved as a reusable profile (TOML or JSON). It's the one settings layout for
+/// every front end: the CLI's flags, prompts and `--config`, the settings objects the browser
+/// passes, Python's dicts and keyword arguments, the C ABI's settings text and job files.
+///
+/// Every field is optional so a profile can pin just the settings it cares about. Command line
+/// flags are layered over the profile with `or`, anything still missing is asked for or defaulted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+#[schemars(title = "Dice settings")]
+#[schemars(extend(
+    "if" = { "properties": { "preset": { "const": "custom" } }, "required": ["preset"] },
+    "then" = { "required": ["thresholds"] }
+))]
+pub struct Profile {
+    /// Layout version, stamped when saved. Profiles without one are from before it was added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(max = VERSION))]
+    pub version: Option<u32>,
+    /// Directory with the face images. CLI only, the browser gets its faces from JS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dice_dir: Option<String>,
+    /// Photo pixels per die, 32 if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub dice_size: Option<u32>,
+    /// Intensity preset, "default" if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("enum" = ["default", "high-contrast", "low-contrast", "bright", "dark", "custom", null]))]
+    pub preset: Option<String>,
+    /// Highest tone for faces One to Five, going up. Wins over the preset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<[u8; 5]>,
+    /// Invert the photo before it's matched to faces.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invert_input: Option<bool>,
+    /// Invert the face images.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invert_dice: Option<bool>,
+    /// Canvas width in pixels, only together with output-height. The photo's size if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_width: Option<u32>,
+    /// Canvas height in pixels, only together with output-width.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_height: Option<u32>,
+    /// Empty pixels between dice.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap: Option<u32>,
+    /// Stamp the settings onto the picture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_overlay: Option<bool>,
+    /// Output format, "png" if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("enum" = ["png", "jpeg", "webp", "avif", null]))]
+    pub format: Option<String>,
+    /// JPEG and AVIF quality, 90 if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1, max = 100))]
+    pub quality: Option<u8>,
+    /// PNG compression effort, "default" if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("enum" = ["fast", "default", "best", null]))]
+    pub png_compression: Option<String>,
+    /// Write PNGs with a palette, much smaller for dice.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub png_indexed: Option<bool>,
+    /// Turns on the variation between dice (photo, turn, jitter and brightness).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+    /// Furthest a die moves each way in pixels, only with a seed. 0.3 if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 0.0))]
+    pub jitter: Option<f32>,
+    /// Most a die gets lighter or darker in percent, only with a seed. 4 if not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(max = 100))]
+    pub brightness_jitter: Option<u8>,
+}
+
+impl Profile {
+    /// Fields set in `self` win, the rest come from `fallback`.
+    ///
+    /// The preset and its thresholds count as one setting: if `self` names either, both come from
+    /// `self`, so e.g. `--preset dark` isn't overruled by thresholds from the `--config` profile.
+    pub fn or(self, fallback: Profile) -> Profile {
+        let (preset, thresholds) = if self.preset.is_some() || self.thresholds.is_some() {
+            (self.preset, self.thresholds)
+        } else {
+            (fallback.preset, fallback.thresholds)
+        };
+        Profile {
+            version: self.version.or(fallback.version),
+            dice_dir: self.dice_dir.or(fallback.dice_dir),
+            dice_size: self.dice_size.or(fallback.dice_size),
+            preset,
+            thresholds,
+            invert_input: self.invert_input.or(fallback.invert_input),
+            invert_dice: self.invert_dice.or(fallback.invert_dice),
+            output_width: self.output_width.or(fallback.output_width),
+            output_height: self.output_height.or(fallback.output_height),
+            gap: self.gap.or(fallback.gap),
+            debug_overlay: self.debug_overlay.or(fallback.debug_overlay),
+            format: self.format.or(fallback.format),
+            quality: self.quality.or(fallback.quality),
+            png_compression: self.png_compression.or(fallback.png_compression),
+            png_indexed: self.png_indexed.or(fallback.png_indexed),
+            seed: self.seed.or(fallback.seed),
+            jitter: self.jitter.or(fallback.jitter),
+            brightness_jitter: self.brightness_jitter.or(fallback.brightness_jitter),
+        }
+    }
+
+    /// The settings embedded in an earlier render, as a profile.
+    pub fn from_settings(settings: &RenderSettings) -> Profile {
+        // A render from a saved grid records "grid", no preset was applied to it. That, and a name this
+        // build doesn't know, re-use as the default rather than asking or failing.
+        let preset = match (settings.preset.as_str(), settings.thresholds) {
+            ("Custom", Some(thresholds)) => IntensityPreset::Custom(thresholds),
+            ("grid", _) => IntensityPreset::Default,
+            (name, _) => IntensityPreset::from_name(name).unwrap_or(IntensityPreset::Default),
+        };
+        Profile {
+            dice_size: Some(settings.dice_size),
+            preset: Some(preset.cli_name().to_string()),
+            thresholds: settings.thresholds,
+            invert_input: Some(settings.invert_input),
+            invert_dice: Some(settings.invert_dice),
+            output_width: settings.output_size.map(|(w, _)| w),
+            output_height: settings.output_size.map(|(_, h)| h),
+            gap: Some(settings.gap),
+            debug_overlay: Some(settings.debug),
+            seed: settings.variation.map(|v| v.seed),
+            jitter: settings.variation.map(|v| v.jitter),
+            brightness_jitter: settings.variation.map(|v| v.brightness),
+            ..Profile::default()
+        }
+    }
+
+    /// The variation the profile asks for, None without a seed.
+    pub fn variation(&self) -> Option<Variation> {
+        let defaults = Variation::default();
+        self.seed.map(|seed| Variation {
+            seed,
+            jitter: self.jitter.unwrap_or(defaults.jitter),
+            brightness: self.brightness_jitter.unwrap_or(defaults.brightness),
+        })
+    }
+
+    pub fn output_size(&self) -> Option<(u32, u32)> {
+        match (self.output_width, self.output_height) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => Some((w, h)),
+            _ => None,
+        }
+    }
+
+    /// Sets the preset, and the thresholds along with it for a custom one.
+    pub fn set_preset(&mut self, preset: IntensityPreset) {
+        self.preset = Some(preset.cli_name().to_string());
+        self.thresholds = match preset {
+            IntensityPreset::Custom(thresholds) => Some(thresholds),
+            _ => None,
+        };
+    }
+
+    /// The preset asked for, custom thresholds winning over a named preset. None if it doesn't say.
+    pub fn preset(&self) -> Result<Option<IntensityPreset>, DiceError> {
+        if let Some(thresholds) = self.thresholds {
+            if thresholds.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(DiceError::InvalidOptions(format!("Thresholds have to go up, got {:?}", thresholds)));
+            }
+            return Ok(Some(IntensityPreset::Custom(thresholds)));
+        }
+        match self.preset.as_deref() {
+            None => Ok(None),
+            Some("custom") => Err(DiceError::InvalidOptions(
+                "The custom preset needs its thresholds, e.g. thresholds = [40, 90, 140, 190, 225]".to_string(),
+            )),
+            Some(name) => IntensityPreset::from_name(name).map(Some).ok_or_else(|| {
+                DiceError::InvalidOptions(format!(
+                    "Unknown preset {} (default, high-contrast, low-contrast, bright or dark, custom needs thresholds)",
+                    name
+                ))
+            }),
+        }
+    }
+
+    /// The options the renderer takes, defaults filled in for anything the profile leaves out.
+    /// Fails on anything the schema would turn down too, e.g. an unknown format.
+    pub fn render_options(&self) -> Result<RenderOptions, DiceError> {
+        let defaults = RenderOptions::default();
+        let quality = self.quality.unwrap_or(90);
+        if self.dice_size == Some(0) || !(1..=100).contains(&quality) {
+            return Err(DiceError::InvalidOptions("Dice size has to be at least 1 and quality 1 to 100".to_string()));
+        }
+        let format = match self.format.as_deref().unwrap_or("png") {
+            "png" => OutputFormat::Png {
+                compression: match self.png_compression.as_deref().unwrap_or("default") {
+                    "fast" => PngCompression::Fast,
+                    "default" => PngCompression::Default,
+                    "best" => PngCompression::Best,
+                    other => {
+                        return Err(DiceError::InvalidOptions(format!(
+                            "Unknown PNG compression {} (fast, default or best)",
+                            other
+                        )))
+                    }
+                },
+                indexed: self.png_indexed.unwrap_or(false),
+            },
+            "jpeg" => OutputFormat::Jpeg { quality },
+            "webp" => OutputFormat::WebP,
+            "avif" => OutputFormat::Avif { quality, speed: 6 },
+            other => {
+                return Err(DiceError::InvalidOptions(format!("Unknown format {} (png, jpeg, webp or avif)", other)))
+            }
+        };
+        Ok(RenderOptions {
+            dice_size: self.dice_size.unwrap_or(defaults.dice_size),
+            preset: self.preset()?.unwrap_or(defaults.preset),
+            invert_input: self.invert_input.unwrap_or(false),
+            invert_dice: self.invert_dice.unwrap_or(false),
+            output_size: self.output_size(),
+            gap: self.gap.unwrap_or(0),
+            debug: self.debug_overlay.unwrap_or(false),
+            format,
+            variation: self.variation(),
+            ..defaults
+        })
+    }
+
+    /// Every setting spelled out the way `render_options` fills it in, e.g. to save what a run used.
+    pub fn with_defaults(&self) -> Result<Profile, DiceError> {
+        let options = self.render_options()?;
+        let variation = options.variation;
+        Ok(Profile {
+            version: Some(VERSION),
+            dice_dir: self.dice_dir.clone(),
+            dice_size: Some(options.dice_size),
+            preset: Some(options.preset.cli_name().to_string()),
+            thresholds: self.thresholds,
+            invert_input: Some(options.invert_input),
+            invert_dice: Some(options.invert_dice),
+            output_width: options.output_size.map(|(w, _)| w),
+            output_height: options.output_size.map(|(_, h)| h),
+            gap: Some(options.gap),
+            debug_overlay: Some(options.debug),
+            format: Some(self.format.clone().unwrap_or_else(|| "png".to_string())),
+            quality: Some(self.quality.unwrap_or(90)),
+            png_compression: Some(self.png_compression.clone().unwrap_or_else(|| "default".to_string())),
+            png_indexed: Some(self.png_indexed.unwrap_or(false)),
+            seed: variation.map(|v| v.seed),
+            jitter: variation.map(|v| v.jitter),
+            brightness_jitter: variation.map(|v| v.brightness),
+        })
+    }
+
+    /// JSON Schema of the current layout, for front ends and job queues to check settings up front.
+    pub fn json_schema() -> String {
+        serde_json::to_string_pretty(&schemars::schema_for!(Profile)).expect("schema is always valid JSON")
+    }
+
+    /// Profile text as the front ends take it, JSON if it starts with `{` and TOML otherwise.
+    pub fn from_text(text: &str) -> Result<Profile, DiceError> {
+        if text.trim_start().starts_with('{') {
+            Profile::from_json(text)
+        } else {
+            Profile::from_toml(text)
+        }
+    }
+
+    /// Reads a profile of any version, migrated to the current one.
+    pub fn from_json(text: &str) -> Result<Profile, DiceError> {
+        let doc = serde_json::from_str(text).map_err(|e| DiceError::Decode(format!("Bad profile: {}", e)))?;
+        Profile::from_value(doc)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.stamped()).expect("profile is always valid JSON")
+    }
+
+    /// Reads a profile of any version, migrated to the current one.
+    pub fn from_toml(text: &str) -> Result<Profile, DiceError> {
+        let doc = toml::from_str(text).map_err(|e| DiceError::Decode(format!("Bad profile: {}", e)))?;
+        Profile::from_value(doc)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(&self.stamped()).expect("profile is always valid TOML")
+    }
+
+    /// A profile already parsed, e.g. from a job queue or a Python dict, migrated like the others.
+    pub fn from_value(mut doc: Value) -> Result<Profile, DiceError> {
+        migrate(&mut doc)?;
+        serde_json::from_value(doc).map_err(|e| DiceError::Decode(format!("Bad profile: {}", e)))
+    }
+
+    /// Whatever version it was read as, a profile is written as the current one.
+    fn stamped(&self) -> Profile {
+        Profile { version: Some(VERSION), ..self.clone() }
+    }
+
+    /// Loads a `.json` profile, anything else is read as TOML.
+    pub fn load(path: &Path) -> Result<Profile, DiceError> {
+        let text = std::fs::read_to_string(path).map_err(|e| DiceError::io(path, e))?;
+        let profile = if is_json(path) {
+            Profile::from_json(&text)
+        } else {
+            Profile::from_toml(&text)
+        };
+        profile.map_err(|e| DiceError::Decode(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Saves as JSON or TOML depending on the extension.
+    pub fn save(&self, path: &Path) -> Result<(), DiceError> {
+        let text = if is_json(path) { self.to_json() } else { self.to_toml() };
+        std::fs::write(path, text).map_err(|e| DiceError::io(path, e))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Brings a parsed profile of any older layout up to `VERSION`, one step per version so each step
+/// only has to know the layout just before it. Profiles from a newer build are turned down, not guessed at.
+pub fn migrate(doc: &mut Value) -> Result<(), DiceError> {
+    let Some(table) = doc.as_object_mut() else {
+        return Err(DiceError::Decode("Bad profile: expected a table of settings".to_string()));
+    };
+    let version = match table.get("version") {
+        None => 0,
+        Some(v) => v.as_u64().filter(|&v| v <= VERSION as u64).ok_or_else(|| {
+            DiceError::Decode(format!("Profile version {} isn't one this build knows (up to {})", v, VERSION))
+        })? as usize,
+    };
+
+    let steps: [fn(&mut Map<String, Value>); VERSION as usize] = [
+        presets_by_cli_name, // 0 to 1
+    ];
+    for step in &steps[version..] {
+        step(table);
+    }
+    table.insert("version".to_string(), Value::from(VERSION));
+    Ok(())
+}
+
+/// Version 0 took any spelling of a preset and rendered unknown names (and "custom" without
+/// thresholds) as the default with a warning. Version 1 only has the kebab-case names and turns
+/// the rest down, so they're written the way version 0 read them.
+fn presets_by_cli_name(table: &mut Map<String, Value>) {
+    let Some(Value::String(name)) = table.get("preset") else {
+        return;
+    };
+    let preset = match IntensityPreset::from_name(name) {
+        Some(preset) => preset.cli_name(),
+        None if name == "custom" && table.contains_key("thresholds") => "custom",
+        None => "default",
+    };
+    table.insert("preset".to_string(), Value::from(preset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(preset: &str, thresholds: Option<[u8; 5]>) -> RenderSettings {
+        RenderSettings {
+            version: "0.1.0".to_string(),
+            preset: preset.to_string(),
+            dice_size: 16,
+            invert_input: false,
+            invert_dice: true,
+            output_size: None,
+            grid: (10, 10),
+            gap: 0,
+            face_hash: String::new(),
+            debug: false,
+            thresholds,
+            variation: None,
+        }
+    }
+
+    #[test]
+    fn version_0_presets_are_migrated() {
+        let profile = Profile::from_toml("preset = \"HighContrast\"\ndice-size = 20\n").unwrap();
+        assert_eq!(profile.version, Some(VERSION));
+        assert_eq!(profile.preset.as_deref(), Some("high-contrast"));
+        assert_eq!(profile.dice_size, Some(20));
+
+        // Version 0 rendered these with the default preset
+        for old in [r#"{"preset": "sepia"}"#, r#"{"preset": "custom"}"#] {
+            let profile = Profile::from_json(old).unwrap();
+            assert_eq!(profile.render_options().unwrap().preset, IntensityPreset::Default);
+        }
+
+        let custom = Profile::from_json(r#"{"preset": "custom", "thresholds": [10, 20, 30, 40, 50]}"#).unwrap();
+        assert_eq!(custom.render_options().unwrap().preset, IntensityPreset::Custom([10, 20, 30, 40, 50]));
+    }
+
+    #[test]
+    fn current_and_newer_versions_arent_rewritten() {
+        let profile = Profile::from_json(r#"{"version": 1, "preset": "sepia"}"#).unwrap();
+        assert_eq!(profile.preset.as_deref(), Some("sepia"));
+        assert!(matches!(Profile::from_json(r#"{"version": 2}"#), Err(DiceError::Decode(_))));
+        assert!(matches!(Profile::from_json("[1, 2]"), Err(DiceError::Decode(_))));
+    }
+
+    #[test]
+    fn unknown_presets_and_bare_custom_are_rejected() {
+        let unknown = Profile { preset: Some("sepia".to_string()), ..Profile::default() };
+        assert!(matches!(unknown.render_options(), Err(DiceError::InvalidOptions(_))));
+        let custom = Profile { preset: Some("custom".to_string()), ..Profile::default() };
+        assert!(matches!(custom.render_options(), Err(DiceError::InvalidOptions(_))));
+        let falling = Profile { thresholds: Some([50, 40, 60, 70, 80]), ..Profile::default() };
+        assert!(matches!(falling.render_options(), Err(DiceError::InvalidOptions(_))));
+    }
+
+    #[test]
+    fn embedded_settings_always_give_a_preset() {
+        let from_grid = Profile::from_settings(&settings("grid", None));
+        assert_eq!(from_grid.render_options().unwrap().preset, IntensityPreset::Default);
+        let unknown = Profile::from_settings(&settings("Sepia", None));
+        assert_eq!(unknown.render_options().unwrap().preset, IntensityPreset::Default);
+        let named = Profile::from_settings(&settings("LowContrast", None));
+        assert_eq!(named.preset.as_deref(), Some("low-contrast"));
+
+        let custom = Profile::from_settings(&settings("Custom", Some([5, 6, 7, 8, 9])));
+        assert_eq!(custom.preset.as_deref(), Some("custom"));
+        assert_eq!(custom.render_options().unwrap().preset, IntensityPreset::Custom([5, 6, 7, 8, 9]));
+    }
+
+    #[test]
+    fn a_named_preset_overrides_thresholds_underneath() {
+        let config = Profile::from_toml("thresholds = [10, 20, 30, 40, 50]\ndice-size = 12\n").unwrap();
+        let flags = Profile { preset: Some("dark".to_string()), ..Profile::default() };
+        let merged = flags.or(config.clone());
+        assert_eq!(merged.render_options().unwrap().preset, IntensityPreset::Dark);
+        assert_eq!(merged.dice_size, Some(12));
+
+        // What the tuning screen settles on goes over the profile the same way
+        let mut tuned = Profile::default();
+        tuned.set_preset(IntensityPreset::Bright);
+        assert_eq!(tuned.or(config.clone()).render_options().unwrap().preset, IntensityPreset::Bright);
+
+        // Thresholds on top win over a named preset underneath, and with nothing on top the profile's stay
+        let flags = Profile { thresholds: Some([1, 2, 3, 4, 5]), ..Profile::default() };
+        let named = Profile { preset: Some("dark".to_string()), ..Profile::default() };
+        assert_eq!(flags.or(named).render_options().unwrap().preset, IntensityPreset::Custom([1, 2, 3, 4, 5]));
+        let kept = Profile::default().or(config);
+        assert_eq!(kept.render_options().unwrap().preset, IntensityPreset::Custom([10, 20, 30, 40, 50]));
+    }
+
+    #[test]
+    fn schema_needs_thresholds_for_custom() {
+        let schema: Value = serde_json::from_str(&Profile::json_schema()).unwrap();
+        assert_eq!(schema["if"]["properties"]["preset"]["const"], "custom");
+        assert_eq!(schema["then"]["required"], serde_json::json!(["thresholds"]));
+    }
+
+    #[test]
+    fn text_is_json_or_toml() {
+        let json = Profile::from_text(" {\"dice-size\": 12}").unwrap();
+        let toml = Profile::from_text("dice-size = 12").unwrap();
+        assert_eq!(json, toml);
+        assert_eq!(Profile::from_text(&json.to_toml()).unwrap(), json);
+    }
+}
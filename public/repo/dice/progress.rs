@@ -0,0 +1,96 @@
+//! Progress reports and cancellation for one run of the pipeline. Both are only ever called from
+//! the thread driving the run, between rows of dice, so a callback doesn't have to be Send
+//! (a JS function can't be) and stopping takes at most one group of rows.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::DiceError;
+
+/// What the pipeline is busy with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Analysing,
+    Rendering,
+    /// Whole-image encoders only, streamed PNGs are encoded while rendering.
+    Encoding,
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Analysing => "analysing",
+            Stage::Rendering => "rendering",
+            Stage::Encoding => "encoding",
+        }
+    }
+}
+
+/// `done` out of `total` rows of dice for the stage (0 or 1 out of 1 for encoding).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub stage: Stage,
+    pub done: u32,
+    pub total: u32,
+}
+
+/// Shared flag to stop a run from somewhere else, e.g. another thread or a signal handler.
+/// Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What to call while a run goes on. `Hooks::none()` for neither.
+#[derive(Default)]
+pub struct Hooks<'a> {
+    progress: Option<Box<dyn FnMut(Progress) + 'a>>,
+    stop: Option<Box<dyn FnMut() -> bool + 'a>>,
+}
+
+impl<'a> Hooks<'a> {
+    pub fn none() -> Hooks<'a> {
+        Hooks::default()
+    }
+
+    /// Called at the start of every stage and after every group of rows.
+    pub fn on_progress(mut self, f: impl FnMut(Progress) + 'a) -> Hooks<'a> {
+        self.progress = Some(Box::new(f));
+        self
+    }
+
+    /// Stops the run with `DiceError::Cancelled` once the token is cancelled.
+    pub fn cancel_with(self, token: CancelToken) -> Hooks<'a> {
+        self.stop_when(move || token.is_cancelled())
+    }
+
+    /// Stops the run with `DiceError::Cancelled` as soon as `f` returns true, it's asked as often as progress.
+    pub fn stop_when(mut self, f: impl FnMut() -> bool + 'a) -> Hooks<'a> {
+        self.stop = Some(Box::new(f));
+        self
+    }
+
+    /// Reports where the run is up to, then checks whether to carry on.
+    /// The stages call it, front ends only need it for work of their own, e.g. encoding a rendered image.
+    pub fn report(&mut self, stage: Stage, done: u32, total: u32) -> Result<(), DiceError> {
+        if let Some(progress) = &mut self.progress {
+            progress(Progress { stage, done, total });
+        }
+        if self.stop.as_mut().is_some_and(|stop| stop()) {
+            return Err(DiceError::Cancelled);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::IsTerminal;
+
+use crate::progress::{Hooks, Progress, Stage};
+
+const BAR_WIDTH: usize = 30;
+
+/// A progress bar on stderr for one photo, or no hooks at all when stderr isn't a terminal
+/// (piped or logged, where a redrawn line would just be noise).
+pub fn progress_bar() -> Hooks<'static> {
+    if !std::io::stderr().is_terminal() {
+        return Hooks::none();
+    }
+    let mut bar = Bar { drawn: None };
+    Hooks::none().on_progress(move |progress| bar.draw(progress))
+}
+
+struct Bar {
+    drawn: Option<(Stage, u32)>, // Stage and percentage on screen
+}
+
+impl Bar {
+    fn draw(&mut self, Progress { stage, done, total }: Progress) {
+        // Finished stages disappear, so the messages after them start on a clean line
+        if done >= total {
+            self.clear();
+            return;
+        }
+        let percent = (done as u64 * 100 / total as u64) as u32;
+        if self.drawn == Some((stage, percent)) {
+            return;
+        }
+        let filled = percent as usize * BAR_WIDTH / 100;
+        let rows = match stage {
+            Stage::Encoding => String::new(),
+            _ => format!(" {}/{} rows", done, total),
+        };
+        eprint!(
+            "\r\x1b[2K{:<9} [{}{}] {:>3}%{}",
+            stage.name(),
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            percent,
+            rows
+        );
+        self.drawn = Some((stage, percent));
+    }
+
+    fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+// Also when the run stops half way, so the error doesn't land on the end of the bar
+impl Drop for Bar {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,100 @@
+use crate::dicelib::IntensityPreset;
+
+/// Asks for settings that weren't given as flags.
+/// Only talks to stdin when interactive, otherwise every question gets its default answer.
+pub struct Prompter {
+    pub interactive: bool,
+}
+
+impl Prompter {
+    fn read_line(&self) -> String {
+        let mut answer = String::new();
+        // A broken stdin reads as an empty answer, which gets the default
+        let _ = std::io::stdin().read_line(&mut answer);
+        answer.trim().to_string()
+    }
+
+    /// y/n question, defaults to no.
+    pub fn yes_no(&self, question: &str) -> bool {
+        if !self.interactive {
+            return false;
+        }
+        eprintln!("{} (y/n):", question);
+        self.read_line().eq_ignore_ascii_case("y")
+    }
+
+    pub fn dice_size(&self) -> u32 {
+        if !self.interactive {
+            return 32;
+        }
+        eprintln!("Enter the dice size you want (e.g., 32 for 32x32 pixels):");
+        match self.read_line().parse() {
+            Ok(size) if size > 0 => size,
+            _ => {
+                eprintln!("Invalid size. Defaulting to 32x32.");
+                32
+            }
+        }
+    }
+
+    pub fn output_size(&self) -> Option<(u32, u32)> {
+        if !self.yes_no("Do you want to set a custom output image size?") {
+            return None;
+        }
+
+        eprintln!("Enter the desired output image width (e.g., 1920 for desktop wallpaper):");
+        let output_width: u32 = match self.read_line().parse() {
+            Ok(width) if width > 0 => width,
+            _ => {
+                eprintln!("Invalid width. Using default width of 1920.");
+                1920
+            }
+        };
+
+        eprintln!("Enter the desired output image height (e.g., 1080 for desktop wallpaper):");
+        let output_height: u32 = match self.read_line().parse() {
+            Ok(height) if height > 0 => height,
+            _ => {
+                eprintln!("Invalid height. Using default height of 1080.");
+                1080
+            }
+        };
+
+        eprintln!("Custom output size set to {}x{}", output_width, output_height);
+        Some((output_width, output_height))
+    }
+
+    pub fn preset(&self) -> IntensityPreset {
+        if !self.interactive {
+            return IntensityPreset::Default;
+        }
+
+        // Ask the user for an intensity preset
+        eprintln!("Pick your intensity preset:");
+        eprintln!("1. Default");
+        eprintln!("2. High Contrast");
+        eprintln!("3. Low Contrast");
+        eprintln!("4. Bright");
+        eprintln!("5. Dark");
+
+        match self.read_line().as_str() {
+            "1" => IntensityPreset::Default,
+            "2" => IntensityPreset::HighContrast,
+            "3" => IntensityPreset::LowContrast,
+            "4" => IntensityPreset::Bright,
+            "5" => IntensityPreset::Dark,
+            _ => {
+                eprintln!("Invalid choice. Defaulting to Default preset.");
+                IntensityPreset::Default
+            }
+        }
+    }
+
+    /// Keeps a double-clicked console window open until the user has read the summary.
+    pub fn wait_for_exit(&self) {
+        if self.interactive {
+            eprintln!("Press Enter to exit...");
+            self.read_line();
+        }
+    }
+}
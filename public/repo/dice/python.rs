@@ -0,0 +1,390 @@
+//! Python bindings (`python` feature), built into the `dice` extension module with maturin. Another thin
+//! front end over the same pipeline: face sets, analysis to a NumPy grid, rendering to encoded bytes or
+//! an RGBA array, and the plan exporters. The types are written out in dice.pyi.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyReadonlyArray2};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use serde_json::{Map, Value};
+
+use crate::dicelib::{DiceGrid, DiceSides};
+use crate::error::DiceError;
+use crate::faceset::FaceSet;
+use crate::inspect;
+use crate::profile::Profile;
+use crate::progress::Hooks;
+use crate::renderer::DiceRenderer;
+use crate::tileset::{DirectoryTiles, GlyphTiles, MemoryTiles, ProceduralDice, TileSet};
+
+/// One exception per kind of failure, all under `dice.DiceError`.
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(dice, DiceError, PyException, "Anything the dice pipeline turns down.");
+    create_exception!(dice, IoError, DiceError, "A file couldn't be read or written.");
+    create_exception!(dice, DecodeError, DiceError, "An image, grid or profile couldn't be understood.");
+    create_exception!(dice, InvalidFaceSetError, DiceError, "The faces don't make a usable set.");
+    create_exception!(dice, InvalidOptionsError, DiceError, "Settings that make no sense, alone or together.");
+    create_exception!(dice, EncodeError, DiceError, "The output couldn't be encoded.");
+    create_exception!(dice, CancelledError, DiceError, "The run was stopped.");
+}
+
+impl From<DiceError> for PyErr {
+    fn from(err: DiceError) -> PyErr {
+        let msg = err.to_string();
+        match err {
+            DiceError::Io { .. } => exceptions::IoError::new_err(msg),
+            DiceError::Decode(_) => exceptions::DecodeError::new_err(msg),
+            DiceError::InvalidFaceSet(_) => exceptions::InvalidFaceSetError::new_err(msg),
+            DiceError::InvalidOptions(_) => exceptions::InvalidOptionsError::new_err(msg),
+            DiceError::Encode(_) => exceptions::EncodeError::new_err(msg),
+            DiceError::Cancelled => exceptions::CancelledError::new_err(msg),
+        }
+    }
+}
+
+/// Tiles to draw with, darkest first (for dice, face One to Six).
+#[pyclass(name = "FaceSet", module = "dice", frozen)]
+struct PyFaceSet {
+    tiles: Box<dyn TileSet + Send + Sync>,
+    report: String, // Which file became which face, empty unless it came from a directory
+}
+
+#[pymethods]
+impl PyFaceSet {
+    /// A dice directory, picked the way the CLI does (faces.toml, otherwise natural order).
+    #[staticmethod]
+    fn open(path: PathBuf) -> PyResult<PyFaceSet> {
+        let face_set = FaceSet::find(&path)?;
+        let tiles = DirectoryTiles::from_face_set(&face_set)?;
+        Ok(PyFaceSet { tiles: Box::new(tiles), report: face_set.report() })
+    }
+
+    /// Encoded images (PNG, JPEG, ...), One first.
+    #[staticmethod]
+    fn from_images(images: Vec<Bound<'_, PyBytes>>) -> PyResult<PyFaceSet> {
+        let images: Vec<&[u8]> = images.iter().map(|image| image.as_bytes()).collect();
+        Ok(PyFaceSet { tiles: Box::new(MemoryTiles::new(&images)?), report: String::new() })
+    }
+
+    /// Dice drawn at whatever size they're needed, no photos.
+    #[staticmethod]
+    fn procedural() -> PyFaceSet {
+        PyFaceSet { tiles: Box::new(ProceduralDice::default()), report: String::new() }
+    }
+
+    /// Characters of a font (the bundled one unless `font` has the bytes of another), sorted darkest first.
+    #[staticmethod]
+    #[pyo3(signature = (chars, font = None))]
+    fn glyphs(chars: &str, font: Option<Vec<u8>>) -> PyResult<PyFaceSet> {
+        let tiles = match font {
+            Some(font) => GlyphTiles::with_font(font, chars)?,
+            None => GlyphTiles::new(chars)?,
+        };
+        Ok(PyFaceSet { tiles: Box::new(tiles), report: String::new() })
+    }
+
+    fn __len__(&self) -> usize {
+        self.tiles.count()
+    }
+
+    /// Average brightness of each tile, 0-255.
+    #[getter]
+    fn tones(&self) -> PyResult<Vec<u8>> {
+        Ok((0..self.tiles.count()).map(|i| self.tiles.tone(i)).collect::<Result<_, _>>()?)
+    }
+
+    #[getter]
+    fn report(&self) -> &str {
+        &self.report
+    }
+
+    #[getter]
+    fn fingerprint(&self) -> String {
+        self.tiles.fingerprint()
+    }
+}
+
+/// A renderer for one set of settings, for any number of photos or grids.
+#[pyclass(name = "Renderer", module = "dice", frozen)]
+struct PyRenderer {
+    renderer: DiceRenderer,
+    profile: Profile,
+}
+
+#[pymethods]
+impl PyRenderer {
+    /// `settings` is profile text (JSON or TOML), a `pathlib.Path` to a profile or a dict, and keyword
+    /// arguments win over it, e.g. `Renderer(faces, dice_size=16, preset="bright")`. Without faces it
+    /// uses the profile's dice-dir, and without either it can analyse but not render.
+    #[new]
+    #[pyo3(signature = (faces = None, settings = None, **overrides))]
+    fn new(
+        faces: Option<PyRef<'_, PyFaceSet>>,
+        settings: Option<&Bound<'_, PyAny>>,
+        overrides: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<PyRenderer> {
+        let mut profile = match settings {
+            Some(settings) => profile_from(settings)?,
+            None => Profile::default(),
+        };
+        if let Some(overrides) = overrides {
+            profile = Profile::from_value(json(overrides.as_any())?)?.or(profile);
+        }
+        let options = profile.render_options()?;
+        let renderer = match (faces, &profile.dice_dir) {
+            (Some(faces), _) => DiceRenderer::from_tile_set(options, faces.tiles.as_ref())?,
+            (None, Some(dir)) => DiceRenderer::from_face_dir(options, Path::new(dir))?.0,
+            (None, None) => DiceRenderer::without_faces(options),
+        };
+        Ok(PyRenderer { renderer, profile })
+    }
+
+    /// Faces 1-6 for a photo (bytes or a path), as a (rows, columns) uint8 array.
+    #[pyo3(signature = (photo, on_progress = None))]
+    fn analyze<'py>(
+        &self,
+        py: Python<'py>,
+        photo: Source<'py>,
+        on_progress: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        let Input::Photo(photo) = photo.load()? else {
+            return Err(DiceError::InvalidOptions("analyze takes a photo, that's already a grid".to_string()).into());
+        };
+        let grid = run(py, on_progress, |hooks| self.renderer.grid_for(&photo, hooks))?;
+        Ok(grid_array(py, &grid))
+    }
+
+    /// The mosaic encoded in the settings' format (PNG unless changed), settings embedded.
+    /// `source` is a photo (bytes or a path) or a grid from `analyze` or `read_grid`.
+    #[pyo3(signature = (source, on_progress = None))]
+    fn render<'py>(
+        &self,
+        py: Python<'py>,
+        source: Source<'py>,
+        on_progress: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = match source.load()? {
+            Input::Photo(photo) => run(py, on_progress, |hooks| self.renderer.process(&photo, hooks))?,
+            Input::Grid(grid) => run(py, on_progress, |hooks| self.renderer.encode_grid(&grid, true, hooks))?,
+        };
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// The mosaic as a (height, width, 4) RGBA uint8 array, ready for `PIL.Image.fromarray`.
+    #[pyo3(signature = (source, on_progress = None))]
+    fn render_image<'py>(
+        &self,
+        py: Python<'py>,
+        source: Source<'py>,
+        on_progress: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let image = match source.load()? {
+            Input::Photo(photo) => run(py, on_progress, |hooks| {
+                let grid = self.renderer.grid_for(&photo, hooks)?;
+                self.renderer.render(&grid, hooks)
+            })?,
+            Input::Grid(grid) => run(py, on_progress, |hooks| self.renderer.render(&grid, hooks))?,
+        };
+        let (w, h) = image.dimensions();
+        let pixels = Array3::from_shape_vec((h as usize, w as usize, 4), image.into_raw()).expect("RGBA is 4 bytes a pixel");
+        Ok(pixels.into_pyarray(py))
+    }
+
+    /// Every setting this renderer uses, as a JSON profile the CLI can load with `--config`.
+    #[getter]
+    fn settings(&self) -> PyResult<String> {
+        Ok(self.profile.with_defaults()?.to_json())
+    }
+
+    #[getter]
+    fn mime_type(&self) -> &'static str {
+        self.renderer.options.format.mime_type()
+    }
+
+    #[getter]
+    fn extension(&self) -> &'static str {
+        self.renderer.options.format.extension()
+    }
+}
+
+/// What to work on: a photo as encoded bytes or a path, or a grid of faces.
+#[derive(FromPyObject)]
+enum Source<'py> {
+    Bytes(Bound<'py, PyBytes>),
+    Path(PathBuf),
+    Grid(PyReadonlyArray2<'py, u8>),
+}
+
+/// A Source read in, owned so the GIL can be let go while it's worked on.
+enum Input {
+    Photo(Vec<u8>),
+    Grid(DiceGrid),
+}
+
+impl Source<'_> {
+    fn load(self) -> PyResult<Input> {
+        Ok(match self {
+            Source::Grid(grid) => Input::Grid(grid_from(&grid)?),
+            Source::Bytes(bytes) => Input::Photo(bytes.as_bytes().to_vec()),
+            Source::Path(path) => Input::Photo(std::fs::read(&path).map_err(|e| DiceError::io(&path, e))?),
+        })
+    }
+}
+
+/// Runs a stage without the GIL, so other Python threads carry on. `on_progress(stage, done, total)`
+/// is called between rows; if it raises, or Ctrl+C comes in, the run stops and that exception is raised.
+fn run<T: Send>(
+    py: Python<'_>,
+    on_progress: Option<Py<PyAny>>,
+    stage: impl FnOnce(&mut Hooks) -> Result<T, DiceError> + Send,
+) -> PyResult<T> {
+    py.detach(|| {
+        let raised: RefCell<Option<PyErr>> = RefCell::new(None);
+        let keep = |result: PyResult<()>| {
+            if let Err(err) = result {
+                raised.borrow_mut().get_or_insert(err);
+            }
+        };
+        let mut hooks = Hooks::none().stop_when(|| {
+            Python::attach(|py| keep(py.check_signals()));
+            raised.borrow().is_some()
+        });
+        if let Some(f) = &on_progress {
+            hooks = hooks.on_progress(|p| {
+                Python::attach(|py| keep(f.call1(py, (p.stage.name(), p.done, p.total)).map(drop)))
+            });
+        }
+        let result = stage(&mut hooks);
+        drop(hooks);
+        match (result, raised.into_inner()) {
+            (Err(DiceError::Cancelled), Some(err)) => Err(err),
+            (result, _) => Ok(result?),
+        }
+    })
+}
+
+/// Settings as profile text (JSON or TOML), a path to a profile file or a dict.
+fn profile_from(settings: &Bound<'_, PyAny>) -> PyResult<Profile> {
+    if let Ok(text) = settings.extract::<String>() {
+        return Ok(Profile::from_text(&text)?);
+    }
+    if let Ok(path) = settings.extract::<PathBuf>() {
+        return Ok(Profile::load(&path)?);
+    }
+    Ok(Profile::from_value(json(settings)?)?)
+}
+
+/// A Python value as JSON for the settings. Keys can be written either way, `dice_size` is `dice-size`.
+fn json(value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if value.is_none() {
+        return Ok(Value::Null);
+    }
+    // bool before int, True is an int to Python
+    if let Ok(flag) = value.extract::<bool>() {
+        return Ok(flag.into());
+    }
+    if let Ok(number) = value.extract::<i64>() {
+        return Ok(number.into());
+    }
+    if let Ok(number) = value.extract::<f64>() {
+        return Ok(number.into());
+    }
+    if let Ok(text) = value.extract::<String>() {
+        return Ok(text.into());
+    }
+    if let Ok(dict) = value.cast::<PyDict>() {
+        let mut table = Map::new();
+        for (key, value) in dict.iter() {
+            table.insert(key.extract::<String>()?.replace('_', "-"), json(&value)?);
+        }
+        return Ok(Value::Object(table));
+    }
+    if let Ok(items) = value.extract::<Vec<Bound<'_, PyAny>>>() {
+        return Ok(Value::Array(items.iter().map(json).collect::<PyResult<_>>()?));
+    }
+    Err(DiceError::InvalidOptions(format!("Can't use {} as a setting", value.repr()?)).into())
+}
+
+fn grid_array<'py>(py: Python<'py>, grid: &DiceGrid) -> Bound<'py, PyArray2<u8>> {
+    let faces = grid.cells.iter().map(|side| side.number()).collect();
+    Array2::from_shape_vec((grid.height as usize, grid.width as usize), faces)
+        .expect("a grid has width x height cells")
+        .into_pyarray(py)
+}
+
+fn grid_from(array: &PyReadonlyArray2<'_, u8>) -> PyResult<DiceGrid> {
+    let faces = array.as_array();
+    let (height, width) = faces.dim();
+    let cells = faces
+        .iter()
+        .map(|&face| {
+            DiceSides::from_number(face)
+                .ok_or_else(|| DiceError::Decode(format!("Bad grid: {} isn't a face, they go 1 to 6", face)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(DiceGrid { width: width as u32, height: height as u32, cells })
+}
+
+/// A grid file (DICEGRID text, as `dice plan` writes it) as a (rows, columns) array of faces.
+#[pyfunction]
+fn read_grid<'py>(py: Python<'py>, text: &str) -> PyResult<Bound<'py, PyArray2<u8>>> {
+    Ok(grid_array(py, &DiceGrid::from_text(text)?))
+}
+
+/// The grid as DICEGRID text, for `dice render --grid` or `read_grid`.
+#[pyfunction]
+fn grid_text(grid: PyReadonlyArray2<'_, u8>) -> PyResult<String> {
+    Ok(grid_from(&grid)?.to_text())
+}
+
+/// How many of each face the grid takes, One first.
+#[pyfunction]
+fn face_counts(grid: PyReadonlyArray2<'_, u8>) -> PyResult<[u64; 6]> {
+    Ok(inspect::face_counts(&grid_from(&grid)?))
+}
+
+/// Bill of materials as CSV, one line per face plus the total.
+#[pyfunction]
+fn bom_csv(grid: PyReadonlyArray2<'_, u8>) -> PyResult<String> {
+    Ok(inspect::bom_csv(&grid_from(&grid)?))
+}
+
+/// The face usage table `dice plan` prints.
+#[pyfunction]
+fn usage_table(grid: PyReadonlyArray2<'_, u8>) -> PyResult<String> {
+    Ok(inspect::usage_table(&grid_from(&grid)?))
+}
+
+/// JSON Schema of the settings `Renderer` takes.
+#[pyfunction]
+fn settings_schema() -> String {
+    Profile::json_schema()
+}
+
+#[pymodule]
+fn dice(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFaceSet>()?;
+    m.add_class::<PyRenderer>()?;
+    m.add_function(wrap_pyfunction!(read_grid, m)?)?;
+    m.add_function(wrap_pyfunction!(grid_text, m)?)?;
+    m.add_function(wrap_pyfunction!(face_counts, m)?)?;
+    m.add_function(wrap_pyfunction!(bom_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(usage_table, m)?)?;
+    m.add_function(wrap_pyfunction!(settings_schema, m)?)?;
+
+    let py = m.py();
+    m.add("DiceError", py.get_type::<exceptions::DiceError>())?;
+    m.add("IoError", py.get_type::<exceptions::IoError>())?;
+    m.add("DecodeError", py.get_type::<exceptions::DecodeError>())?;
+    m.add("InvalidFaceSetError", py.get_type::<exceptions::InvalidFaceSetError>())?;
+    m.add("InvalidOptionsError", py.get_type::<exceptions::InvalidOptionsError>())?;
+    m.add("EncodeError", py.get_type::<exceptions::EncodeError>())?;
+    m.add("CancelledError", py.get_type::<exceptions::CancelledError>())?;
+    Ok(())
+}
@@ -0,0 +1,441 @@
+use std::io::Write;
+use std::path::Path;
+
+use image::{imageops, DynamicImage, GrayImage, RgbaImage};
+
+use crate::dicelib::{
+    add_reference_text, build_grid, mosaic_size, prepare_photo, Dice, DiceGrid, DiceSides, IntensityPreset, Tiles,
+    REFERENCE_BAR_HEIGHT,
+};
+use crate::encode::{encode_image, stream_png, OutputFormat, PngCompression};
+use crate::error::DiceError;
+use crate::faceset::FaceSet;
+use crate::metadata::RenderSettings;
+use crate::parallel::{for_each_chunk, map_rows, threads};
+use crate::progress::{Hooks, Stage};
+use crate::tileset::{DirectoryTiles, MemoryTiles, TileSet};
+use crate::variation::Variation;
+
+/// Everything that decides how a photo turns into dice.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub dice_size: u32, // Photo pixels per die
+    pub face_size: Option<u32>, // Size the faces get drawn at, None draws them at dice_size
+    pub preset: IntensityPreset,
+    pub invert_input: bool,
+    pub invert_dice: bool,
+    pub output_size: Option<(u32, u32)>, // Custom canvas, None keeps the photo size
+    pub gap: u32, // Empty pixels between dice
+    pub debug: bool, // Stamp the reference text onto the output
+    pub format: OutputFormat,
+    pub variation: Option<Variation>, // Seeded differences between dice, None draws them all alike
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            dice_size: 32,
+            face_size: None,
+            preset: IntensityPreset::Default,
+            invert_input: false,
+            invert_dice: false,
+            output_size: None,
+            gap: 0,
+            debug: false,
+            format: OutputFormat::Png { compression: PngCompression::Default, indexed: false },
+            variation: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    fn face_size(&self) -> u32 {
+        self.face_size.unwrap_or(self.dice_size)
+    }
+}
+
+/// The pipeline every front end goes through, one stage per method:
+/// `load` → `preprocess` → `analyze` → `render` → `encode`, or all of them with `process`.
+/// The faces are loaded once, so one renderer can do any number of photos (or saved grids).
+/// The slow stages take `Hooks` for progress reports and cancelling, see progress.rs.
+pub struct DiceRenderer {
+    pub options: RenderOptions,
+    dice: Vec<Dice>, // Resized and inverted per the options, One first. Empty when only analysing.
+    tiles: Tiles, // The same faces ready to blit
+    face_hash: String,
+}
+
+impl DiceRenderer {
+    /// A renderer that can analyse but not draw, for plans and stats.
+    pub fn without_faces(options: RenderOptions) -> DiceRenderer {
+        DiceRenderer { options, dice: Vec::new(), tiles: Tiles::new(&[]), face_hash: String::new() }
+    }
+
+    /// Faces as encoded images (PNG, JPEG, ...), One to Six.
+    pub fn from_face_bytes<B: AsRef<[u8]>>(options: RenderOptions, faces: &[B]) -> Result<DiceRenderer, DiceError> {
+        if faces.len() != 6 {
+            return Err(DiceError::InvalidFaceSet(format!("expected 6 face images, got {}", faces.len())));
+        }
+        DiceRenderer::from_tile_set(options, &MemoryTiles::new(faces)?)
+    }
+
+    /// Faces from a dice directory, picked the way FaceSet does (faces.toml, otherwise natural order).
+    /// The face set comes back too so front ends can report which file became which face.
+    pub fn from_face_dir(options: RenderOptions, dir: &Path) -> Result<(DiceRenderer, FaceSet), DiceError> {
+        let face_set = FaceSet::find(dir)?;
+        Ok((DiceRenderer::from_face_set(options, &face_set)?, face_set))
+    }
+
+    /// The faces of a set already found, e.g. to report on it before loading.
+    /// With variation the manifest's variants get mixed in, as more photos of their face.
+    pub fn from_face_set(options: RenderOptions, face_set: &FaceSet) -> Result<DiceRenderer, DiceError> {
+        DiceRenderer::from_tile_set(options, &DirectoryTiles::from_face_set(face_set)?)
+    }
+
+    /// Tiles from anywhere, see tileset.rs. Takes six, one per tone band, darkest first.
+    pub fn from_tile_set(options: RenderOptions, set: &dyn TileSet) -> Result<DiceRenderer, DiceError> {
+        if set.count() != 6 {
+            return Err(DiceError::InvalidFaceSet(format!(
+                "the grid has six tones, so it takes six tiles, got {}",
+                set.count()
+            )));
+        }
+        if let Some(variation) = &options.variation {
+            if !(variation.jitter >= 0.0 && variation.jitter.is_finite()) || variation.brightness > 100 {
+                return Err(DiceError::InvalidOptions(format!(
+                    "Jitter has to be 0 or more pixels and brightness 0-100%, got {} and {}",
+                    variation.jitter, variation.brightness
+                )));
+            }
+        }
+        let size = options.face_size();
+        let mut dice = Vec::with_capacity(6);
+        for index in 0..6 {
+            let side = DiceSides::from_number(index as u8 + 1).expect("six sides");
+            let variants = if options.variation.is_some() { set.variants(index) } else { 0 };
+            let main = std::iter::once(set.render(index, size));
+            for tile in main.chain((0..variants).map(|v| set.render_variant(index, v, size))) {
+                let mut tile = tile?;
+                if tile.dimensions() != (size, size) {
+                    return Err(DiceError::InvalidFaceSet(format!(
+                        "tile {} came out {}x{}, asked for {}x{}",
+                        index + 1,
+                        tile.width(),
+                        tile.height(),
+                        size,
+                        size
+                    )));
+                }
+                if options.invert_dice {
+                    imageops::invert(&mut tile);
+                }
+                dice.push(Dice { side, image: DynamicImage::ImageRgba8(tile) });
+            }
+        }
+        let tiles = Tiles::varied(&dice, options.variation);
+        Ok(DiceRenderer { options, dice, tiles, face_hash: set.fingerprint() })
+    }
+
+    /// The faces as they get drawn, One first, the main photo of each face before any variants.
+    /// Empty for a renderer made `without_faces`.
+    pub fn dice(&self) -> &[Dice] {
+        &self.dice
+    }
+
+    /// Fingerprint of the face files, see metadata.rs.
+    pub fn face_hash(&self) -> &str {
+        &self.face_hash
+    }
+
+    /// Width and height of one die in the output.
+    pub fn die_size(&self) -> (u32, u32) {
+        match self.dice.first() {
+            Some(d) => (d.image.width(), d.image.height()),
+            None => (self.options.face_size(), self.options.face_size()),
+        }
+    }
+
+    /// Stage 1: decodes a photo (any format the image crate knows) to grayscale.
+    pub fn load(bytes: &[u8]) -> Result<GrayImage, DiceError> {
+        image::load_from_memory(bytes)
+            .map(DynamicImage::into_luma8)
+            .map_err(|e| DiceError::Decode(format!("Couldn't decode the photo: {}", e)))
+    }
+
+    /// Stage 2: inverts, crops to a square and fits the photo to the output canvas.
+    pub fn preprocess(&self, photo: GrayImage) -> GrayImage {
+        prepare_photo(photo, self.options.invert_input, self.options.output_size)
+    }
+
+    /// Stage 3: picks a face for every block of the prepared photo.
+    pub fn analyze(&self, prepared: &GrayImage, hooks: &mut Hooks) -> Result<DiceGrid, DiceError> {
+        let size = self.options.dice_size;
+        if size == 0 {
+            return Err(DiceError::InvalidOptions("Dice size has to be at least 1".to_string()));
+        }
+        // It's one pass over the photo, too quick to be worth reporting row by row
+        let rows = prepared.height() / size;
+        hooks.report(Stage::Analysing, 0, rows)?;
+        let grid = build_grid(prepared, size, size, &self.options.preset);
+        if grid.width == 0 || grid.height == 0 {
+            return Err(DiceError::InvalidOptions(format!(
+                "{}x{} is smaller than a single {}x{} die",
+                prepared.width(),
+                prepared.height(),
+                size,
+                size
+            )));
+        }
+        hooks.report(Stage::Analysing, rows, rows)?;
+        Ok(grid)
+    }
+
+    /// Stage 4: lays out the dice for a grid, with the debug overlay if asked for.
+    /// The rows of dice are drawn in parallel, straight into their part of the image,
+    /// a group of rows (one per thread) between progress reports.
+    pub fn render(&self, grid: &DiceGrid, hooks: &mut Hooks) -> Result<RgbaImage, DiceError> {
+        let strip = self.start_render(grid)?;
+        let (ow, oh) = self.output_size(grid)?;
+        let band_h = self.die_size().1 + self.options.gap;
+        let mut oi = RgbaImage::new(ow, oh);
+        hooks.report(Stage::Rendering, 0, grid.height)?;
+        if oi.is_empty() {
+            return Ok(oi);
+        }
+        // The bottom band has no gap below it, its chunk is shorter
+        let band_bytes = band_h as usize * ow as usize * 4;
+        let step = threads();
+        for (group, bands) in oi.chunks_mut(band_bytes * step).enumerate() {
+            let first = group * step;
+            for_each_chunk(bands, band_bytes, |i, chunk| {
+                self.draw_band(grid, (first + i) as u32, strip.as_ref(), chunk);
+            });
+            let done = ((first + step) as u32).min(grid.height);
+            hooks.report(Stage::Rendering, done, grid.height)?;
+        }
+        Ok(oi)
+    }
+
+    /// Width and height of the rendered mosaic, an error if it's too big for one image.
+    pub fn output_size(&self, grid: &DiceGrid) -> Result<(u32, u32), DiceError> {
+        mosaic_size(grid, self.die_size(), self.options.gap)
+    }
+
+    /// Stage 4 a row of dice at a time: `each` gets every band (full width, one die high plus the gap
+    /// below) top to bottom. Only a few bands are in memory at a time (one per thread), however big the mosaic.
+    pub fn render_bands(
+        &self,
+        grid: &DiceGrid,
+        hooks: &mut Hooks,
+        mut each: impl FnMut(&RgbaImage) -> Result<(), DiceError>,
+    ) -> Result<(), DiceError> {
+        let strip = self.start_render(grid)?;
+        let (ow, _) = self.output_size(grid)?;
+        hooks.report(Stage::Rendering, 0, grid.height)?;
+        let step = threads() as u32;
+        for first in (0..grid.height).step_by(step as usize) {
+            let last = (first + step).min(grid.height);
+            for band in map_rows(first..last, |grid_y| self.band(grid, ow, grid_y, strip.as_ref())) {
+                each(&band)?;
+            }
+            hooks.report(Stage::Rendering, last, grid.height)?;
+        }
+        Ok(())
+    }
+
+    /// Checks there are faces to draw with, and makes the overlay strip if it's wanted.
+    fn start_render(&self, grid: &DiceGrid) -> Result<Option<RgbaImage>, DiceError> {
+        if self.dice.is_empty() {
+            return Err(DiceError::InvalidFaceSet("no faces loaded to render with".to_string()));
+        }
+        self.reference_strip(grid)
+    }
+
+    /// Row `grid_y` of dice as an image of its own, `ow` wide.
+    fn band(&self, grid: &DiceGrid, ow: u32, grid_y: u32, strip: Option<&RgbaImage>) -> RgbaImage {
+        let (_, dh) = self.die_size();
+        let band_h = if grid_y + 1 < grid.height { dh + self.options.gap } else { dh };
+        let mut band = RgbaImage::new(ow, band_h);
+        self.draw_band(grid, grid_y, strip, &mut band);
+        band
+    }
+
+    /// Draws row `grid_y` of dice into `band` (raw RGBA, starting at the top of the row),
+    /// with the overlay drawn over it if it reaches that far down.
+    fn draw_band(&self, grid: &DiceGrid, grid_y: u32, strip: Option<&RgbaImage>, band: &mut [u8]) {
+        self.tiles.draw_band(grid, self.options.gap, grid_y, band);
+        let y = (grid_y * (self.die_size().1 + self.options.gap)) as usize;
+        // The overlay covers the top rows completely, whatever dice are under it
+        if let Some(strip) = strip.filter(|s| !s.is_empty()) {
+            let row_bytes = strip.width() as usize * 4;
+            for (r, row) in band.chunks_exact_mut(row_bytes).enumerate() {
+                match strip.as_raw().get((y + r) * row_bytes..(y + r + 1) * row_bytes) {
+                    Some(src) => row.copy_from_slice(src),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// The debug overlay on its own: the top REFERENCE_BAR_HEIGHT rows of the mosaic.
+    fn reference_strip(&self, grid: &DiceGrid) -> Result<Option<RgbaImage>, DiceError> {
+        if !self.options.debug {
+            return Ok(None);
+        }
+        let (ow, oh) = self.output_size(grid)?;
+        let mut strip = RgbaImage::new(ow, oh.min(REFERENCE_BAR_HEIGHT));
+        add_reference_text(&mut strip, self.die_size(), grid.width * grid.height, (ow, oh))?;
+        Ok(Some(strip))
+    }
+
+    /// Whether the PNG gets a palette. Not with variation: shifted and brightened dice blend into
+    /// colours no palette known up front holds, they'd snap to the nearest plain ones. Truecolour then.
+    fn indexed(&self) -> bool {
+        matches!(self.options.format, OutputFormat::Png { indexed: true, .. }) && self.options.variation.is_none()
+    }
+
+    /// Every colour the mosaic can contain (raw RGBA, repeats and all), for the palette of an indexed PNG:
+    /// the faces, the gaps and the overlay. Known before the first row is drawn.
+    fn colours(&self, grid: &DiceGrid) -> Result<Vec<u8>, DiceError> {
+        let mut colours: Vec<u8> = self.dice.iter().flat_map(|d| d.image.to_rgba8().into_raw()).collect();
+        if self.options.gap > 0 {
+            colours.extend_from_slice(&[0, 0, 0, 0]);
+        }
+        if let Some(strip) = self.reference_strip(grid)? {
+            colours.extend_from_slice(strip.as_raw());
+        }
+        Ok(colours)
+    }
+
+    /// Stages 4 and 5 streamed: writes the mosaic as a PNG band by band, so peak memory is about
+    /// one row of dice instead of the whole image. Only for PNG, the other encoders want the full image.
+    /// Cancelling leaves `out` with a partial PNG.
+    pub fn write_png<W: Write>(
+        &self,
+        grid: &DiceGrid,
+        from_grid: bool,
+        out: W,
+        hooks: &mut Hooks,
+    ) -> Result<(), DiceError> {
+        let OutputFormat::Png { compression, .. } = self.options.format else {
+            return Err(DiceError::InvalidOptions(format!(
+                "Only PNG can be written as it renders, not {}",
+                self.options.format.extension()
+            )));
+        };
+
+        let indexed = self.indexed();
+        let colours = if indexed { self.colours(grid)? } else { Vec::new() };
+        let settings = self.settings(grid, from_grid);
+        stream_png(out, self.output_size(grid)?, compression, indexed, &colours, Some(&settings), |write_rows| {
+            self.render_bands(grid, hooks, |band| write_rows(band.as_raw()))
+        })
+    }
+
+    /// Stage 5: encodes the rendered image in the chosen format, with the settings embedded.
+    /// `from_grid` says the grid was loaded rather than worked out, the preset then doesn't apply.
+    pub fn encode(&self, grid: &DiceGrid, image: &RgbaImage, from_grid: bool) -> Result<Vec<u8>, DiceError> {
+        let settings = self.settings(grid, from_grid);
+        match self.options.format {
+            // Same palette as write_png, so streamed or not the pixels come out the same
+            OutputFormat::Png { compression, .. } => {
+                let mut bytes = Vec::new();
+                let indexed = self.indexed();
+                let colours = if indexed { self.colours(grid)? } else { Vec::new() };
+                stream_png(&mut bytes, image.dimensions(), compression, indexed, &colours, Some(&settings), |write_rows| {
+                    write_rows(image.as_raw())
+                })?;
+                Ok(bytes)
+            }
+            _ => encode_image(image, &self.options.format, Some(&settings)),
+        }
+    }
+
+    /// What goes into the output metadata, enough to render it again.
+    pub fn settings(&self, grid: &DiceGrid, from_grid: bool) -> RenderSettings {
+        let preset = &self.options.preset;
+        RenderSettings {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            preset: if from_grid { "grid" } else { preset.name() }.to_string(),
+            dice_size: self.options.dice_size,
+            invert_input: self.options.invert_input,
+            invert_dice: self.options.invert_dice,
+            output_size: self.options.output_size,
+            grid: (grid.width, grid.height),
+            gap: self.options.gap,
+            face_hash: self.face_hash.clone(),
+            debug: self.options.debug,
+            thresholds: match preset {
+                IntensityPreset::Custom(thresholds) if !from_grid => Some(*thresholds),
+                _ => None,
+            },
+            variation: self.options.variation,
+        }
+    }
+
+    /// Load, preprocess and analyze in one go.
+    pub fn grid_for(&self, photo: &[u8], hooks: &mut Hooks) -> Result<DiceGrid, DiceError> {
+        self.analyze(&self.preprocess(DiceRenderer::load(photo)?), hooks)
+    }
+
+    /// The whole pipeline: photo bytes in, encoded mosaic out.
+    /// PNG is streamed, so the full-size image never exists uncompressed.
+    pub fn process(&self, photo: &[u8], hooks: &mut Hooks) -> Result<Vec<u8>, DiceError> {
+        let grid = self.grid_for(photo, hooks)?;
+        self.encode_grid(&grid, false, hooks)
+    }
+
+    /// Stages 4 and 5 for a grid, streamed for PNG.
+    pub fn encode_grid(&self, grid: &DiceGrid, from_grid: bool, hooks: &mut Hooks) -> Result<Vec<u8>, DiceError> {
+        match self.options.format {
+            OutputFormat::Png { .. } => {
+                let mut bytes = Vec::new();
+                self.write_png(grid, from_grid, &mut bytes, hooks)?;
+                Ok(bytes)
+            }
+            _ => {
+                let image = self.render(grid, hooks)?;
+                hooks.report(Stage::Encoding, 0, 1)?;
+                let bytes = self.encode(grid, &image, from_grid)?;
+                hooks.report(Stage::Encoding, 1, 1)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tileset::ProceduralDice;
+
+    fn render_png(indexed: bool, variation: Option<Variation>) -> Vec<u8> {
+        let options = RenderOptions {
+            dice_size: 6,
+            format: OutputFormat::Png { compression: PngCompression::Fast, indexed },
+            variation,
+            ..RenderOptions::default()
+        };
+        let photo = GrayImage::from_fn(60, 48, |x, y| image::Luma([((x * 4 + y) % 256) as u8]));
+        let renderer = DiceRenderer::from_tile_set(options, &ProceduralDice::default()).unwrap();
+        let grid = renderer.analyze(&renderer.preprocess(photo), &mut Hooks::none()).unwrap();
+        renderer.encode_grid(&grid, false, &mut Hooks::none()).unwrap()
+    }
+
+    fn colour_type(png: &[u8]) -> png::ColorType {
+        png::Decoder::new(std::io::Cursor::new(png)).read_info().unwrap().info().color_type
+    }
+
+    #[test]
+    fn plain_dice_get_a_palette() {
+        assert_eq!(colour_type(&render_png(true, None)), png::ColorType::Indexed);
+    }
+
+    #[test]
+    fn varied_dice_stay_truecolour() {
+        let variation = Some(Variation { seed: 5, brightness: 20, ..Variation::default() });
+        let indexed = render_png(true, variation);
+        assert_eq!(colour_type(&indexed), png::ColorType::Rgba);
+        assert_eq!(indexed, render_png(false, variation));
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use base64::Engine;
+use image::{imageops, Rgba, RgbaImage};
+
+/// How an image gets drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    Kitty,  // kitty graphics protocol (kitty, ghostty, WezTerm)
+    Iterm,  // iTerm2 inline images (iTerm2, WezTerm)
+    Sixel,  // foot, mlterm, xterm -ti vt340, ...
+    Blocks, // ▀ half blocks in truecolor, works nearly everywhere
+}
+
+impl Graphics {
+    /// `auto` isn't a protocol, use `detect` for that.
+    pub fn from_name(name: &str) -> Option<Graphics> {
+        match name {
+            "kitty" => Some(Graphics::Kitty),
+            "iterm" => Some(Graphics::Iterm),
+            "sixel" => Some(Graphics::Sixel),
+            "blocks" => Some(Graphics::Blocks),
+            _ => None,
+        }
+    }
+
+    /// Best guess from the environment, there's no reliable way to ask the terminal without reading its reply.
+    pub fn detect() -> Graphics {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+
+        if std::env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || program == "ghostty" {
+            Graphics::Kitty
+        } else if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL") == "iTerm2" {
+            Graphics::Iterm
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") || term == "contour" {
+            Graphics::Sixel
+        } else {
+            Graphics::Blocks
+        }
+    }
+}
+
+/// Size of the terminal in cells, and of one cell in pixels (guessed when the terminal won't say).
+fn terminal_cells() -> (u32, u32, u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.columns > 0 && size.rows > 0 => {
+            let (cols, rows) = (size.columns as u32, size.rows as u32);
+            let (cell_w, cell_h) = if size.width > 0 && size.height > 0 {
+                (size.width as u32 / cols, size.height as u32 / rows)
+            } else {
+                (8, 16)
+            };
+            (cols, rows, cell_w.max(1), cell_h.max(1))
+        }
+        _ => (80, 24, 8, 16),
+    }
+}
+
+/// Scales the image down (never up) to fit in `max_w` x `max_h`, keeping the aspect ratio.
+fn fit(image: &RgbaImage, max_w: u32, max_h: u32) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    let scale = (max_w as f32 / w as f32).min(max_h as f32 / h as f32).min(1.0);
+    let (nw, nh) = (((w as f32 * scale) as u32).max(1), ((h as f32 * scale) as u32).max(1));
+    // thumbnail averages whole areas, so a die shrunk to a pixel keeps its tone
+    imageops::thumbnail(image, nw, nh)
+}
+
+/// Drops the alpha, transparent pixels come out black like in the JPEG output.
+fn flatten(p: &Rgba<u8>) -> [u8; 3] {
+    let a = p[3] as u32;
+    [(p[0] as u32 * a / 255) as u8, (p[1] as u32 * a / 255) as u8, (p[2] as u32 * a / 255) as u8]
+}
+
+/// Escape sequences that draw a preview of `image` filling at most the width of the terminal
+/// and all but a couple of its rows.
+pub fn show_image(image: &RgbaImage, graphics: Graphics) -> Vec<u8> {
+    let (cols, rows, cell_w, cell_h) = terminal_cells();
+    let rows = rows.saturating_sub(2).max(4);
+
+    match graphics {
+        Graphics::Kitty => kitty(&fit(image, cols * cell_w, rows * cell_h)),
+        Graphics::Iterm => iterm(&fit(image, cols * cell_w, rows * cell_h)),
+        Graphics::Sixel => sixel(&fit(image, cols * cell_w, rows * cell_h)),
+        // Each cell holds two pixels, one above the other
+        Graphics::Blocks => blocks(&fit(image, cols, rows * 2)),
+    }
+}
+
+fn png_bytes(image: &RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // Writing a PNG into memory can't fail for a valid image
+    let _ = image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png);
+    bytes
+}
+
+/// Kitty wants the base64 PNG in chunks of at most 4096 bytes, `m=1` on all but the last.
+fn kitty(image: &RgbaImage) -> Vec<u8> {
+    let data = base64::engine::general_purpose::STANDARD.encode(png_bytes(image));
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    let mut out = Vec::with_capacity(data.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            out.extend_from_slice(format!("\x1b_Gf=100,a=T,m={};", more).as_bytes());
+        } else {
+            out.extend_from_slice(format!("\x1b_Gm={};", more).as_bytes());
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+    out.push(b'\n');
+    out
+}
+
+fn iterm(image: &RgbaImage) -> Vec<u8> {
+    let png = png_bytes(image);
+    let mut out = format!(
+        "\x1b]1337;File=inline=1;size={};width={}px;height={}px;preserveAspectRatio=1:",
+        png.len(),
+        image.width(),
+        image.height()
+    );
+    out += &base64::engine::general_purpose::STANDARD.encode(&png);
+    out += "\x07\n";
+    out.into_bytes()
+}
+
+/// Sixel: up to 256 palette colours, the image drawn in bands six pixels high,
+/// one pass per colour used in the band.
+fn sixel(image: &RgbaImage) -> Vec<u8> {
+    let (w, h) = image.dimensions();
+    let rgba: Vec<u8> = image.pixels().flat_map(|p| {
+        let [r, g, b] = flatten(p);
+        [r, g, b, 255]
+    }).collect();
+    let nq = color_quant::NeuQuant::new(10, 256, &rgba);
+    let palette = nq.color_map_rgb();
+    let indices: Vec<u8> = rgba.chunks_exact(4).map(|p| nq.index_of(p) as u8).collect();
+
+    let mut out = format!("\x1bPq\"1;1;{};{}", w, h);
+    for (i, c) in palette.chunks_exact(3).enumerate() {
+        // Sixel colours are percentages
+        let pct = |v: u8| v as u32 * 100 / 255;
+        let _ = write!(out, "#{};2;{};{};{}", i, pct(c[0]), pct(c[1]), pct(c[2]));
+    }
+
+    for band in (0..h).step_by(6) {
+        let band_h = (h - band).min(6);
+        let mut used = [false; 256];
+        for y in band..band + band_h {
+            for x in 0..w {
+                used[indices[(y * w + x) as usize] as usize] = true;
+            }
+        }
+
+        for colour in (0..256).filter(|&c| used[c]) {
+            let _ = write!(out, "#{}", colour);
+            let mut run: Option<(char, u32)> = None;
+            for x in 0..w {
+                let mut bits = 0u8;
+                for dy in 0..band_h {
+                    if indices[((band + dy) * w + x) as usize] as usize == colour {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = (b'?' + bits) as char;
+                run = match run {
+                    Some((c, n)) if c == ch => Some((c, n + 1)),
+                    Some((c, n)) => {
+                        push_run(&mut out, c, n);
+                        Some((ch, 1))
+                    }
+                    None => Some((ch, 1)),
+                };
+            }
+            if let Some((c, n)) = run {
+                push_run(&mut out, c, n);
+            }
+            out.push('$'); // back to the start of the band for the next colour
+        }
+        out.push('-'); // next band
+    }
+    out += "\x1b\\\n";
+    out.into_bytes()
+}
+
+fn push_run(out: &mut String, ch: char, count: u32) {
+    if count > 3 {
+        let _ = write!(out, "!{}{}", count, ch);
+    } else {
+        for _ in 0..count {
+            out.push(ch);
+        }
+    }
+}
+
+/// Upper pixel as the foreground colour of ▀, lower pixel as the background.
+fn blocks(image: &RgbaImage) -> Vec<u8> {
+    let (w, h) = image.dimensions();
+    let mut out = String::new();
+    for y in (0..h).step_by(2) {
+        for x in 0..w {
+            let [r, g, b] = flatten(image.get_pixel(x, y));
+            if y + 1 < h {
+                let [br, bg, bb] = flatten(image.get_pixel(x, y + 1));
+                let _ = write!(out, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀", r, g, b, br, bg, bb);
+            } else {
+                let _ = write!(out, "\x1b[0m\x1b[38;2;{};{};{}m▀", r, g, b);
+            }
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    out.into_bytes()
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use image::DynamicImage;
+
+use crate::dicelib::{Dice, DiceGrid, DiceSides};
+
+/// How a grid gets printed to the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    Unicode, // ⚀ ⚁ ⚂ ⚃ ⚄ ⚅
+    Digits,  // 1-6
+    Ansi,    // truecolor blocks tinted like the face images
+}
+
+impl TextStyle {
+    pub fn from_name(name: &str) -> Option<TextStyle> {
+        match name {
+            "unicode" => Some(TextStyle::Unicode),
+            "digits" => Some(TextStyle::Digits),
+            "ansi" => Some(TextStyle::Ansi),
+            _ => None,
+        }
+    }
+
+    /// Terminal columns one die takes up. ANSI blocks are doubled so they come out roughly square.
+    pub fn columns_per_die(&self) -> u32 {
+        match self {
+            TextStyle::Ansi => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Picks the dice size (in input pixels) so the grid fits in `max_cols` terminal columns.
+pub fn dice_size_for_columns(input_width: u32, max_cols: u32, style: TextStyle) -> u32 {
+    let dice_across = (max_cols / style.columns_per_die()).max(1);
+    input_width.div_ceil(dice_across).max(1)
+}
+
+fn unicode_face(side: DiceSides) -> char {
+    match side {
+        DiceSides::One => '⚀',
+        DiceSides::Two => '⚁',
+        DiceSides::Three => '⚂',
+        DiceSides::Four => '⚃',
+        DiceSides::Five => '⚄',
+        DiceSides::Six => '⚅',
+    }
+}
+
+fn digit_face(side: DiceSides) -> char {
+    match side {
+        DiceSides::One => '1',
+        DiceSides::Two => '2',
+        DiceSides::Three => '3',
+        DiceSides::Four => '4',
+        DiceSides::Five => '5',
+        DiceSides::Six => '6',
+    }
+}
+
+/// Average colour of a face image, transparent pixels count as black.
+pub fn face_colour(image: &DynamicImage) -> [u8; 3] {
+    let rgba = image.to_rgba8();
+    let mut totals = [0u64; 3];
+    for p in rgba.pixels() {
+        for c in 0..3 {
+            totals[c] += p[c] as u64 * p[3] as u64 / 255;
+        }
+    }
+    let count = (rgba.width() as u64 * rgba.height() as u64).max(1);
+    [
+        (totals[0] / count) as u8,
+        (totals[1] / count) as u8,
+        (totals[2] / count) as u8,
+    ]
+}
+
+/// Renders the grid as text, one line per row of dice.
+/// `dice` is only used for the ANSI colours.
+pub fn render_text(grid: &DiceGrid, dice: &[Dice], style: TextStyle) -> String {
+    let colours: Vec<(DiceSides, [u8; 3])> = match style {
+        TextStyle::Ansi => dice.iter().map(|d| (d.side, face_colour(&d.image))).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut out = String::new();
+    for row in grid.rows() {
+        for &side in row {
+            match style {
+                TextStyle::Unicode => out.push(unicode_face(side)),
+                TextStyle::Digits => out.push(digit_face(side)),
+                TextStyle::Ansi => {
+                    let [r, g, b] = colours
+                        .iter()
+                        .find(|(s, _)| *s == side)
+                        .map(|(_, c)| *c)
+                        .unwrap_or([0, 0, 0]);
+                    let _ = write!(out, "\x1b[48;2;{};{};{}m  ", r, g, b);
+                }
+            }
+        }
+        if style == TextStyle::Ansi {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn tiny_grid() -> DiceGrid {
+        DiceGrid {
+            width: 3,
+            height: 2,
+            cells: (1..=6).filter_map(DiceSides::from_number).collect(),
+        }
+    }
+
+    #[test]
+    fn dice_size_fits_the_columns() {
+        assert_eq!(dice_size_for_columns(800, 80, TextStyle::Digits), 10);
+        assert_eq!(dice_size_for_columns(801, 80, TextStyle::Digits), 11);
+        // Two columns a die
+        assert_eq!(dice_size_for_columns(800, 80, TextStyle::Ansi), 20);
+        // A photo narrower than the terminal gets one pixel per die
+        assert_eq!(dice_size_for_columns(30, 80, TextStyle::Unicode), 1);
+        // Room for less than one die still draws one die across
+        assert_eq!(dice_size_for_columns(800, 1, TextStyle::Ansi), 800);
+        assert_eq!(dice_size_for_columns(800, 0, TextStyle::Digits), 800);
+    }
+
+    #[test]
+    fn plain_text_is_a_character_per_die() {
+        assert_eq!(render_text(&tiny_grid(), &[], TextStyle::Digits), "123\n456\n");
+        assert_eq!(render_text(&tiny_grid(), &[], TextStyle::Unicode), "⚀⚁⚂\n⚃⚄⚅\n");
+    }
+
+    #[test]
+    fn ansi_is_two_tinted_spaces_per_die() {
+        let face = |c: u8| DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([c, c, c, 255])));
+        let dice = vec![
+            Dice { side: DiceSides::One, image: face(10) },
+            Dice { side: DiceSides::Two, image: face(200) },
+        ];
+        let cells = vec![DiceSides::One, DiceSides::Two, DiceSides::Two, DiceSides::Three];
+        let grid = DiceGrid { width: 2, height: 2, cells };
+
+        let text = render_text(&grid, &dice, TextStyle::Ansi);
+        // Every row resets at the end, a face without an image comes out black
+        assert_eq!(
+            text,
+            "\x1b[48;2;10;10;10m  \x1b[48;2;200;200;200m  \x1b[0m\n\
+             \x1b[48;2;200;200;200m  \x1b[48;2;0;0;0m  \x1b[0m\n"
+        );
+    }
+}
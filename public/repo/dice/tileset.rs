@@ -0,0 +1,351 @@
+//! Where the tiles of a mosaic come from. The renderer only needs a `TileSet`: how many tiles there are,
+//! how dark each one is and the tile drawn at a given size. Face directories and the buffers the browser
+//! hands over are tile sets, and so are dice drawn from scratch and font glyphs; other crates can bring
+//! their own without touching the pipeline.
+
+use std::path::Path;
+
+use ab_glyph::{Font, FontVec, PxScale};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::dicelib::BUNDLED_FONT;
+use crate::error::DiceError;
+use crate::faceset::FaceSet;
+use crate::metadata::FaceHasher;
+
+/// Tiles for a mosaic, darkest tone band first: tile 0 is drawn where the photo is darkest.
+/// For dice that's face One to Six, whatever they look like (`invert_dice` and `invert_input` sort
+/// out which way round a set reads). The grid has six bands, so `DiceRenderer` takes sets of six.
+pub trait TileSet {
+    /// How many tiles there are.
+    fn count(&self) -> usize;
+
+    /// Tile `index` drawn `size` pixels square.
+    fn render(&self, index: usize, size: u32) -> Result<RgbaImage, DiceError>;
+
+    /// Average brightness of tile `index`, 0-255. Measured on a small render unless the set knows better.
+    fn tone(&self, index: usize) -> Result<u8, DiceError> {
+        Ok(tone(&self.render(index, 32)?))
+    }
+
+    /// How many other looks tile `index` has (more photos of the same face), mixed in with a seed.
+    fn variants(&self, _index: usize) -> usize {
+        0
+    }
+
+    /// Variant `variant` of tile `index`, see `variants`.
+    fn render_variant(&self, index: usize, variant: usize, _size: u32) -> Result<RgbaImage, DiceError> {
+        Err(DiceError::InvalidFaceSet(format!("tile {} has no variant {}", index + 1, variant + 1)))
+    }
+
+    /// Tells tile sets apart, it goes into the output settings so a re-render can warn about
+    /// different tiles. Empty when the set can't say.
+    fn fingerprint(&self) -> String {
+        String::new()
+    }
+}
+
+/// Average brightness of a tile, transparent pixels count as black.
+pub fn tone(tile: &RgbaImage) -> u8 {
+    let mut total = 0u64;
+    for p in tile.pixels() {
+        // Rec. 601 luma, the same weights `into_luma8` uses for the input
+        let luma = (p[0] as u64 * 299 + p[1] as u64 * 587 + p[2] as u64 * 114) / 1000;
+        total += luma * p[3] as u64 / 255;
+    }
+    let count = (tile.width() as u64 * tile.height() as u64).max(1);
+    (total / count) as u8
+}
+
+/// Decoded images, each with any other photos of it: the faces of a directory or of buffers in memory.
+struct Photos {
+    faces: Vec<Vec<DynamicImage>>, // One first, the main photo first
+    hash: String,
+}
+
+impl Photos {
+    /// `(face, label, bytes)` in order, the main photo of a face before its variants.
+    fn decode<'a>(files: impl Iterator<Item = (usize, String, &'a [u8])>) -> Result<Photos, DiceError> {
+        let mut faces: Vec<Vec<DynamicImage>> = Vec::new();
+        let mut hasher = FaceHasher::new();
+        let mut variants = Vec::new();
+        for (face, label, bytes) in files {
+            let image = image::load_from_memory(bytes).map_err(|e| {
+                DiceError::InvalidFaceSet(format!("couldn't load face {} ({}): {}", face + 1, label, e))
+            })?;
+            if image.width() == 0 || image.height() == 0 {
+                return Err(DiceError::InvalidFaceSet(format!("face {} ({}) is empty", face + 1, label)));
+            }
+            if face == faces.len() {
+                hasher.update(bytes);
+                faces.push(vec![image]);
+            } else {
+                variants.push(bytes);
+                faces[face].push(image);
+            }
+        }
+        // Variants after the main photos, so a set without any hashes the way it always has
+        for bytes in variants {
+            hasher.update(bytes);
+        }
+        Ok(Photos { faces, hash: hasher.finish() })
+    }
+
+    fn render(&self, index: usize, photo: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        let image = self
+            .faces
+            .get(index)
+            .and_then(|photos| photos.get(photo))
+            .ok_or_else(|| DiceError::InvalidFaceSet(format!("there's no face {}", index + 1)))?;
+        Ok(image.resize_exact(size, size, imageops::FilterType::Lanczos3).to_rgba8())
+    }
+}
+
+/// The faces of a dice directory, picked the way `FaceSet` does (faces.toml, otherwise natural order),
+/// with the manifest's variants as more photos of their face.
+pub struct DirectoryTiles {
+    photos: Photos,
+}
+
+impl DirectoryTiles {
+    pub fn open(dir: &Path) -> Result<DirectoryTiles, DiceError> {
+        DirectoryTiles::from_face_set(&FaceSet::find(dir)?)
+    }
+
+    /// The files of a face set already found, e.g. to report on it before loading.
+    pub fn from_face_set(face_set: &FaceSet) -> Result<DirectoryTiles, DiceError> {
+        let mut files = Vec::new();
+        for (index, face) in face_set.faces.iter().enumerate() {
+            for path in std::iter::once(&face.path).chain(&face.variants) {
+                let bytes = std::fs::read(path).map_err(|e| DiceError::io(path, e))?;
+                files.push((index, path.display().to_string(), bytes));
+            }
+        }
+        let photos = Photos::decode(files.iter().map(|(index, label, bytes)| (*index, label.clone(), bytes.as_slice())))?;
+        Ok(DirectoryTiles { photos })
+    }
+}
+
+impl TileSet for DirectoryTiles {
+    fn count(&self) -> usize {
+        self.photos.faces.len()
+    }
+
+    fn render(&self, index: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        self.photos.render(index, 0, size)
+    }
+
+    fn variants(&self, index: usize) -> usize {
+        self.photos.faces.get(index).map_or(0, |photos| photos.len().saturating_sub(1))
+    }
+
+    fn render_variant(&self, index: usize, variant: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        self.photos.render(index, variant + 1, size)
+    }
+
+    fn fingerprint(&self) -> String {
+        self.photos.hash.clone()
+    }
+}
+
+/// Encoded images (PNG, JPEG, ...) already in memory, one per tile, e.g. handed over from JS.
+pub struct MemoryTiles {
+    photos: Photos,
+}
+
+impl MemoryTiles {
+    pub fn new<B: AsRef<[u8]>>(images: &[B]) -> Result<MemoryTiles, DiceError> {
+        let images = images.iter().enumerate().map(|(i, bytes)| (i, format!("image {}", i + 1), bytes.as_ref()));
+        Ok(MemoryTiles { photos: Photos::decode(images)? })
+    }
+}
+
+impl TileSet for MemoryTiles {
+    fn count(&self) -> usize {
+        self.photos.faces.len()
+    }
+
+    fn render(&self, index: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        self.photos.render(index, 0, size)
+    }
+
+    fn fingerprint(&self) -> String {
+        self.photos.hash.clone()
+    }
+}
+
+/// Dice drawn from scratch at whatever size they're wanted, no photos needed: rounded squares with
+/// round pips, faces One to Six.
+#[derive(Debug, Clone, Copy)]
+pub struct ProceduralDice {
+    pub body: Rgba<u8>,
+    pub pips: Rgba<u8>,
+}
+
+impl Default for ProceduralDice {
+    fn default() -> ProceduralDice {
+        ProceduralDice { body: Rgba([245, 245, 240, 255]), pips: Rgba([20, 20, 20, 255]) }
+    }
+}
+
+impl ProceduralDice {
+    /// Pip centres on a face, as fractions of its size.
+    fn pip_positions(face: usize) -> &'static [(f32, f32)] {
+        const L: f32 = 0.27;
+        const M: f32 = 0.5;
+        const H: f32 = 0.73;
+        match face {
+            1 => &[(M, M)],
+            2 => &[(L, L), (H, H)],
+            3 => &[(L, L), (M, M), (H, H)],
+            4 => &[(L, L), (H, L), (L, H), (H, H)],
+            5 => &[(L, L), (H, L), (M, M), (L, H), (H, H)],
+            _ => &[(L, L), (H, L), (L, M), (H, M), (L, H), (H, H)],
+        }
+    }
+}
+
+impl TileSet for ProceduralDice {
+    fn count(&self) -> usize {
+        6
+    }
+
+    fn render(&self, index: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        let pips = ProceduralDice::pip_positions(index + 1);
+        let (corner, pip) = (0.16, 0.09);
+        // 4x4 samples a pixel, enough for smooth edges at any size
+        const SAMPLES: u32 = 4;
+        Ok(RgbaImage::from_fn(size, size, |x, y| {
+            let (mut body, mut ink) = (0u32, 0u32);
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let u = (x as f32 + (sx as f32 + 0.5) / SAMPLES as f32) / size as f32;
+                    let v = (y as f32 + (sy as f32 + 0.5) / SAMPLES as f32) / size as f32;
+                    // Distance outside the rounded square, 0 inside it
+                    let (du, dv) = ((corner - u).max(u - (1.0 - corner)).max(0.0), (corner - v).max(v - (1.0 - corner)).max(0.0));
+                    if du.hypot(dv) > corner {
+                        continue;
+                    }
+                    body += 1;
+                    if pips.iter().any(|&(pu, pv)| (u - pu).hypot(v - pv) <= pip) {
+                        ink += 1;
+                    }
+                }
+            }
+            let total = SAMPLES * SAMPLES;
+            let mix = |c: usize| {
+                let colour = (self.body[c] as u32 * (body - ink) + self.pips[c] as u32 * ink) / body.max(1);
+                colour as u8
+            };
+            let alpha = (body * 255 / total) as u8;
+            Rgba([mix(0), mix(1), mix(2), alpha])
+        }))
+    }
+
+    fn fingerprint(&self) -> String {
+        let mut hasher = FaceHasher::new();
+        hasher.update(b"procedural dice");
+        hasher.update(&self.body.0);
+        hasher.update(&self.pips.0);
+        hasher.finish()
+    }
+}
+
+/// Characters of a font as tiles, like ASCII art: ink on paper, sorted darkest first whatever order they
+/// were given in. The bundled font (DejaVu Sans Bold) unless another one is given.
+pub struct GlyphTiles {
+    font: FontVec,
+    font_bytes: Vec<u8>,
+    glyphs: Vec<char>,
+    ink: Rgba<u8>,
+    paper: Rgba<u8>,
+}
+
+impl GlyphTiles {
+    /// Black on white, e.g. `GlyphTiles::new("@#+-. ")`.
+    pub fn new(glyphs: &str) -> Result<GlyphTiles, DiceError> {
+        GlyphTiles::with_font(BUNDLED_FONT.to_vec(), glyphs)
+    }
+
+    /// Glyphs from a TrueType / OpenType font file.
+    pub fn with_font(font_bytes: Vec<u8>, glyphs: &str) -> Result<GlyphTiles, DiceError> {
+        let font = FontVec::try_from_vec(font_bytes.clone())
+            .map_err(|e| DiceError::InvalidFaceSet(format!("couldn't load the font: {}", e)))?;
+        let mut tiles = GlyphTiles {
+            font,
+            font_bytes,
+            glyphs: glyphs.chars().collect(),
+            ink: Rgba([0, 0, 0, 255]),
+            paper: Rgba([255, 255, 255, 255]),
+        };
+        tiles.sort()?;
+        Ok(tiles)
+    }
+
+    /// Other colours, sorted again since that can turn the order round.
+    pub fn colours(mut self, ink: Rgba<u8>, paper: Rgba<u8>) -> Result<GlyphTiles, DiceError> {
+        self.ink = ink;
+        self.paper = paper;
+        self.sort()?;
+        Ok(self)
+    }
+
+    /// The glyphs, darkest first.
+    pub fn glyphs(&self) -> &[char] {
+        &self.glyphs
+    }
+
+    fn sort(&mut self) -> Result<(), DiceError> {
+        let mut toned = Vec::with_capacity(self.glyphs.len());
+        for (index, &glyph) in self.glyphs.iter().enumerate() {
+            toned.push((self.tone(index)?, glyph));
+        }
+        // Stable, so glyphs that measure the same keep the order they were given in
+        toned.sort_by_key(|&(tone, _)| tone);
+        self.glyphs = toned.into_iter().map(|(_, glyph)| glyph).collect();
+        Ok(())
+    }
+}
+
+impl TileSet for GlyphTiles {
+    fn count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    fn render(&self, index: usize, size: u32) -> Result<RgbaImage, DiceError> {
+        let &glyph = self
+            .glyphs
+            .get(index)
+            .ok_or_else(|| DiceError::InvalidFaceSet(format!("there's no glyph {}", index + 1)))?;
+        let mut tile = RgbaImage::from_pixel(size, size, self.paper);
+        let glyph = self.font.glyph_id(glyph).with_scale(PxScale::from(size as f32));
+        // Whitespace has no outline, it's just paper
+        if let Some(outline) = self.font.outline_glyph(glyph) {
+            let bounds = outline.px_bounds();
+            // Centred, whatever the glyph's own offsets
+            let left = (size as f32 - bounds.width()) / 2.0;
+            let top = (size as f32 - bounds.height()) / 2.0;
+            outline.draw(|x, y, coverage| {
+                let (px, py) = (x as i64 + left.round() as i64, y as i64 + top.round() as i64);
+                if px < 0 || py < 0 || px >= size as i64 || py >= size as i64 {
+                    return;
+                }
+                let p = tile.get_pixel_mut(px as u32, py as u32);
+                for c in 0..4 {
+                    let mixed = self.paper[c] as f32 + (self.ink[c] as f32 - self.paper[c] as f32) * coverage.min(1.0);
+                    p[c] = mixed.round() as u8;
+                }
+            });
+        }
+        Ok(tile)
+    }
+
+    fn fingerprint(&self) -> String {
+        let mut hasher = FaceHasher::new();
+        hasher.update(&self.font_bytes);
+        hasher.update(self.glyphs.iter().collect::<String>().as_bytes());
+        hasher.update(&self.ink.0);
+        hasher.update(&self.paper.0);
+        hasher.finish()
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, terminal};
+use image::GrayImage;
+
+use crate::dicelib::{map_intensity_to_dice_side, prepare_photo, IntegralImage, IntensityPreset, PRESETS};
+use crate::error::DiceError;
+use crate::faceset::FaceSet;
+use crate::textout::face_colour;
+
+const HIST_WIDTH: u32 = 64; // columns, 4 tones each
+const HIST_HEIGHT: u32 = 8;
+const PANEL_HEIGHT: u32 = HIST_HEIGHT + 10;
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What the tuning screen lets you change.
+#[derive(Debug, Clone, Copy)]
+pub struct Tuning {
+    pub dice_size: u32,
+    pub preset: IntensityPreset,
+    pub invert_input: bool,
+    pub invert_dice: bool,
+}
+
+/// Average colour of each face, One first. Only the colours are needed, so the faces are loaded small.
+pub fn face_colours(dice_dir: &str) -> Result<[[u8; 3]; 6], DiceError> {
+    let face_set = FaceSet::find(Path::new(dice_dir))?;
+    let mut colours = [[0u8; 3]; 6];
+    for (colour, face) in colours.iter_mut().zip(&face_set.faces) {
+        let image = image::open(&face.path).map_err(|e| DiceError::image(&face.path, e))?;
+        *colour = face_colour(&image.thumbnail(32, 32));
+    }
+    Ok(colours)
+}
+
+/// Switches the terminal to the tuning screen and back again when dropped, whatever happens in between.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Screen, DiceError> {
+        terminal::enable_raw_mode().map_err(term_err)?;
+        execute!(std::io::stderr(), terminal::EnterAlternateScreen, cursor::Hide).map_err(term_err)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Threshold `i` moved by `by`, staying strictly between its neighbours so every face keeps at
+/// least one tone. Thresholds from --thresholds can already be packed too tight for that (up to
+/// 255, or two alike), then it stays where it is.
+fn moved_threshold(mut thresholds: [u8; 5], i: usize, by: i32) -> [u8; 5] {
+    let low = if i == 0 { 0 } else { thresholds[i - 1] as i32 + 1 };
+    let high = if i == 4 { 254 } else { thresholds[i + 1] as i32 - 1 };
+    if low <= high {
+        thresholds[i] = (thresholds[i] as i32 + by).clamp(low, high) as u8;
+    }
+    thresholds
+}
+
+fn term_err(e: std::io::Error) -> DiceError {
+    DiceError::io("terminal", e)
+}
+
+struct Tuner<'a> {
+    photo: &'a GrayImage,
+    output_size: Option<(u32, u32)>,
+    colours: [[u8; 3]; 6],
+    tuning: Tuning,
+    selected: usize, // which threshold the arrow keys move
+    message: String,
+    // Block averages for the current dice size and inversion, the thresholds only remap them.
+    // The integral image only changes with the inversion, a new dice size just reads it again.
+    integral: IntegralImage,
+    square: u32,
+    across: u32,
+    down: u32,
+    averages: Vec<u8>,
+}
+
+impl Tuner<'_> {
+    fn prepare(&mut self) {
+        let prepared = prepare_photo(self.photo.clone(), self.tuning.invert_input, self.output_size);
+        self.square = prepared.width().min(prepared.height());
+        self.integral = IntegralImage::new(&prepared);
+        self.analyse();
+    }
+
+    fn analyse(&mut self) {
+        let size = self.tuning.dice_size;
+        (self.across, self.down, self.averages) = self.integral.block_averages(size, size);
+    }
+
+    fn face_colour(&self, tone: u8) -> [u8; 3] {
+        let [r, g, b] = self.colours[map_intensity_to_dice_side(tone, &self.tuning.preset).number() as usize - 1];
+        if self.tuning.invert_dice {
+            [255 - r, 255 - g, 255 - b]
+        } else {
+            [r, g, b]
+        }
+    }
+
+    /// Changes the number of dice across by about a tenth, at least one.
+    fn change_dice_count(&mut self, more: bool) {
+        let across = (self.square / self.tuning.dice_size).max(1);
+        let step = (across / 10).max(1);
+        let across = if more { across + step } else { across.saturating_sub(step).max(1) };
+        self.tuning.dice_size = (self.square / across).max(1);
+        self.analyse();
+    }
+
+    fn move_threshold(&mut self, by: i32) {
+        let thresholds = moved_threshold(self.tuning.preset.thresholds(), self.selected, by);
+        self.tuning.preset = IntensityPreset::Custom(thresholds);
+    }
+
+    fn next_preset(&mut self) {
+        let next = PRESETS.iter().position(|p| *p == self.tuning.preset).map_or(0, |i| (i + 1) % PRESETS.len());
+        self.tuning.preset = PRESETS[next];
+    }
+
+    /// Whole screen in one string, so it's drawn in one write without flicker.
+    fn draw(&self, cols: u32, rows: u32) -> String {
+        let mut out = String::from("\x1b[H\x1b[2J");
+
+        // Side by side if there's room for the preview next to the panel, otherwise stacked
+        let side_by_side = cols >= HIST_WIDTH + 2 + 20;
+        let (preview_cols, preview_rows, panel_x, panel_y) = if side_by_side {
+            (cols - HIST_WIDTH - 2, rows, cols - HIST_WIDTH, 0)
+        } else {
+            (cols, rows.saturating_sub(PANEL_HEIGHT).max(4), 0, rows.saturating_sub(PANEL_HEIGHT).max(4))
+        };
+        self.draw_preview(&mut out, preview_cols, preview_rows);
+        self.draw_panel(&mut out, panel_x, panel_y);
+        out
+    }
+
+    /// The grid as ▀ half blocks, two dice per cell, scaled to fit.
+    fn draw_preview(&self, out: &mut String, cols: u32, rows: u32) {
+        if self.across == 0 || self.down == 0 {
+            let _ = write!(out, "\x1b[1;1HThe dice are bigger than the photo");
+            return;
+        }
+        let scale = (cols as f32 / self.across as f32).min(rows as f32 * 2.0 / self.down as f32);
+        let width = ((self.across as f32 * scale) as u32).clamp(1, cols);
+        let height = ((self.down as f32 * scale) as u32).clamp(1, rows * 2);
+        let die = |x: u32, y: u32| {
+            let gx = ((x as f32 / scale) as u32).min(self.across - 1);
+            let gy = ((y as f32 / scale) as u32).min(self.down - 1);
+            self.face_colour(self.averages[(gy * self.across + gx) as usize])
+        };
+
+        for y in (0..height).step_by(2) {
+            let _ = write!(out, "\x1b[{};1H", y / 2 + 1);
+            for x in 0..width {
+                let [r, g, b] = die(x, y);
+                let [br, bg, bb] = if y + 1 < height { die(x, y + 1) } else { [0, 0, 0] };
+                let _ = write!(out, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀", r, g, b, br, bg, bb);
+            }
+            out.push_str("\x1b[0m");
+        }
+    }
+
+    /// Histogram of the block tones with the face bands under it, then the settings and keys.
+    fn draw_panel(&self, out: &mut String, x: u32, y: u32) {
+        let mut line = y;
+        let mut goto = |out: &mut String| {
+            line += 1;
+            let _ = write!(out, "\x1b[{};{}H", line, x + 1);
+        };
+
+        let tones_per_column = 256 / HIST_WIDTH;
+        let mut counts = [0u32; HIST_WIDTH as usize];
+        for &avg in &self.averages {
+            counts[(avg as u32 / tones_per_column) as usize] += 1;
+        }
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+
+        // Bars in eighths of a row, tallest column fills the whole height
+        for row in (0..HIST_HEIGHT).rev() {
+            goto(out);
+            for (column, &count) in counts.iter().enumerate() {
+                let eighths = (count as u64 * HIST_HEIGHT as u64 * 8).div_ceil(max as u64) as u32;
+                let ch = match eighths.saturating_sub(row * 8) {
+                    0 => ' ',
+                    n => BARS[(n.min(8) - 1) as usize],
+                };
+                let [r, g, b] = self.face_colour((column as u32 * tones_per_column) as u8);
+                let _ = write!(out, "\x1b[38;2;{};{};{}m{}", r, g, b, ch);
+            }
+            out.push_str("\x1b[0m");
+        }
+
+        // Face bands, each number on the tones it gets
+        goto(out);
+        for column in 0..HIST_WIDTH {
+            let tone = (column * tones_per_column) as u8;
+            let [r, g, b] = self.face_colour(tone);
+            let face = map_intensity_to_dice_side(tone, &self.tuning.preset).number();
+            let fg = if (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000 > 128 { 30 } else { 97 };
+            let _ = write!(out, "\x1b[{}m\x1b[48;2;{};{};{}m{}", fg, r, g, b, face);
+        }
+        out.push_str("\x1b[0m");
+
+        // The thresholds between the bands, the one the arrows move highlighted
+        goto(out);
+        let thresholds = self.tuning.preset.thresholds();
+        let mut markers = vec![" ".to_string(); HIST_WIDTH as usize];
+        for (i, &t) in thresholds.iter().enumerate() {
+            let column = (t as u32 / tones_per_column) as usize;
+            markers[column] = if i == self.selected { "\x1b[1;33m▲\x1b[0m".to_string() } else { "▲".to_string() };
+        }
+        out.push_str(&markers.concat());
+
+        goto(out);
+        let _ = write!(
+            out,
+            "Preset: {:<13} Thresholds: {}",
+            self.tuning.preset.cli_name(),
+            thresholds.map(|t| t.to_string()).join(" ")
+        );
+        goto(out);
+        let _ = write!(
+            out,
+            "Dice: {}x{} ({}px)  Invert photo: {}  dice: {}",
+            self.across,
+            self.down,
+            self.tuning.dice_size,
+            if self.tuning.invert_input { "yes" } else { "no" },
+            if self.tuning.invert_dice { "yes" } else { "no" }
+        );
+        goto(out);
+        let _ = write!(out, "\x1b[33m{}\x1b[0m", self.message);
+        goto(out);
+        out.push_str("←/→ pick threshold   ↑/↓ move it (PgUp/PgDn by 8)");
+        goto(out);
+        out.push_str("p preset   i invert photo   d invert dice   +/- dice count");
+        goto(out);
+        out.push_str("s save profile   Enter render   q quit");
+    }
+}
+
+/// Live tuning screen on stderr. Returns the settings to render with when Enter is pressed,
+/// `Cancelled` on q / Esc / Ctrl+C. `save` writes the profile and says where it went.
+pub fn tune(
+    photo: &GrayImage,
+    output_size: Option<(u32, u32)>,
+    colours: [[u8; 3]; 6],
+    start: Tuning,
+    save: &dyn Fn(&Tuning) -> Result<String, DiceError>,
+) -> Result<Tuning, DiceError> {
+    let mut tuner = Tuner {
+        photo,
+        output_size,
+        colours,
+        tuning: start,
+        selected: 0,
+        message: String::new(),
+        integral: IntegralImage::new(&GrayImage::new(0, 0)),
+        square: 0,
+        across: 0,
+        down: 0,
+        averages: Vec::new(),
+    };
+    tuner.prepare();
+
+    let _screen = Screen::enter()?;
+    let mut stderr = std::io::stderr();
+    loop {
+        let (cols, rows) = terminal::size().map_err(term_err)?;
+        queue!(stderr, crossterm::style::Print(tuner.draw(cols as u32, rows as u32))).map_err(term_err)?;
+        stderr.flush().map_err(term_err)?;
+
+        let key = match event::read().map_err(term_err)? {
+            Event::Key(key @ KeyEvent { kind: KeyEventKind::Press, .. }) => key,
+            _ => continue, // resizes just redraw
+        };
+        tuner.message.clear();
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Err(DiceError::Cancelled),
+            KeyCode::Char('q') | KeyCode::Esc => return Err(DiceError::Cancelled),
+            KeyCode::Enter => return Ok(tuner.tuning),
+            KeyCode::Left => tuner.selected = tuner.selected.saturating_sub(1),
+            KeyCode::Right | KeyCode::Tab => tuner.selected = (tuner.selected + 1).min(4),
+            KeyCode::Up => tuner.move_threshold(1),
+            KeyCode::Down => tuner.move_threshold(-1),
+            KeyCode::PageUp => tuner.move_threshold(8),
+            KeyCode::PageDown => tuner.move_threshold(-8),
+            KeyCode::Char('p') => tuner.next_preset(),
+            KeyCode::Char('i') => {
+                tuner.tuning.invert_input = !tuner.tuning.invert_input;
+                tuner.prepare();
+            }
+            KeyCode::Char('d') => tuner.tuning.invert_dice = !tuner.tuning.invert_dice,
+            KeyCode::Char('+') | KeyCode::Char('=') => tuner.change_dice_count(true),
+            KeyCode::Char('-') => tuner.change_dice_count(false),
+            KeyCode::Char('s') => {
+                tuner.message = match save(&tuner.tuning) {
+                    Ok(path) => format!("Profile saved to {}", path),
+                    Err(err) => err.to_string(),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_stay_between_their_neighbours() {
+        let thresholds = [40, 90, 140, 190, 225];
+        assert_eq!(moved_threshold(thresholds, 1, 8), [40, 98, 140, 190, 225]);
+        assert_eq!(moved_threshold(thresholds, 1, 100), [40, 139, 140, 190, 225]);
+        assert_eq!(moved_threshold(thresholds, 0, -100), [0, 90, 140, 190, 225]);
+        assert_eq!(moved_threshold(thresholds, 4, 100), [40, 90, 140, 190, 254]);
+    }
+
+    #[test]
+    fn packed_thresholds_at_255_dont_move() {
+        let thresholds = [251, 252, 253, 254, 255];
+        for i in 1..5 {
+            for by in [-8, -1, 1, 8] {
+                assert_eq!(moved_threshold(thresholds, i, by), thresholds);
+            }
+        }
+        // The first one still has room below
+        assert_eq!(moved_threshold(thresholds, 0, -1), [250, 252, 253, 254, 255]);
+        assert_eq!(moved_threshold([10, 10, 10, 30, 40], 1, 1), [10, 10, 10, 30, 40]);
+    }
+}
@@ -0,0 +1,107 @@
+//! Seeded randomness so a mosaic looks built by hand rather than stamped out: each die can be another
+//! photo of its face, turned a quarter (One, Four and Five look the same turned), nudged by a fraction
+//! of a pixel and a little lighter or darker. Every choice comes from the seed and the die's place in
+//! the grid, so the same seed always gives the same picture, whatever order the rows are drawn in.
+
+use image::RgbaImage;
+
+use crate::dicelib::DiceSides;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variation {
+    pub seed: u32,
+    pub jitter: f32,    // Furthest a die moves, in pixels, each way
+    pub brightness: u8, // Most a die gets lighter or darker, in percent
+}
+
+impl Default for Variation {
+    fn default() -> Variation {
+        Variation { seed: 0, jitter: 0.3, brightness: 4 }
+    }
+}
+
+/// What one die gets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    pub tile: usize,       // Which of the face's tiles (photo and turn)
+    pub shift: (f32, f32), // Pixels right and down
+    pub brightness: f32,   // Factor for the colour channels
+}
+
+impl Pick {
+    /// Nothing to do but copy the tile.
+    pub fn is_plain(&self) -> bool {
+        self.shift == (0.0, 0.0) && self.brightness == 1.0
+    }
+}
+
+impl Variation {
+    /// Faces that look the same a quarter turn round, so they can be drawn turned.
+    pub fn turns(side: DiceSides) -> bool {
+        matches!(side, DiceSides::One | DiceSides::Four | DiceSides::Five)
+    }
+
+    /// The choices for the die at `x`, `y` out of `tiles` tiles for its face.
+    pub fn pick(&self, x: u32, y: u32, tiles: usize) -> Pick {
+        let mut random = SplitMix(splitmix(self.seed as u64) ^ ((y as u64) << 32 | x as u64));
+        let tile = (random.next() % tiles.max(1) as u64) as usize;
+        // Keep drawing numbers even when a setting is off, so turning one on doesn't reshuffle the others
+        let shift = (random.between(self.jitter), random.between(self.jitter));
+        let brightness = 1.0 + random.between(self.brightness as f32 / 100.0);
+        Pick { tile, shift, brightness }
+    }
+}
+
+/// Draws `tile` moved by `pick.shift` (bilinear, the edges stretched into the gap it leaves) and
+/// brightened by `pick.brightness` into `band`, raw RGBA rows of `row_bytes` with the die starting at byte `x`.
+pub fn draw_picked(tile: &RgbaImage, pick: &Pick, band: &mut [u8], row_bytes: usize, x: usize, rows: usize) {
+    let (w, h) = tile.dimensions();
+    let (dx, dy) = pick.shift;
+    // Source position and weight of the next pixel along, clamped to the tile
+    let sample = |at: f32, len: u32| {
+        let floor = at.floor();
+        let clamp = |v: f32| v.clamp(0.0, len as f32 - 1.0) as u32;
+        (clamp(floor), clamp(floor + 1.0), at - floor)
+    };
+    let columns: Vec<(u32, u32, f32)> = (0..w).map(|tx| sample(tx as f32 - dx, w)).collect();
+
+    for ty in 0..(h as usize).min(rows) {
+        let (y0, y1, fy) = sample(ty as f32 - dy, h);
+        let out = &mut band[ty * row_bytes + x..][..w as usize * 4];
+        for (px, &(x0, x1, fx)) in out.chunks_exact_mut(4).zip(&columns) {
+            let (a, b) = (tile.get_pixel(x0, y0).0, tile.get_pixel(x1, y0).0);
+            let (c, d) = (tile.get_pixel(x0, y1).0, tile.get_pixel(x1, y1).0);
+            for i in 0..4 {
+                let top = a[i] as f32 + (b[i] as f32 - a[i] as f32) * fx;
+                let bottom = c[i] as f32 + (d[i] as f32 - c[i] as f32) * fx;
+                let mut v = top + (bottom - top) * fy;
+                if i < 3 {
+                    v *= pick.brightness;
+                }
+                px[i] = v.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+/// SplitMix64, small and good enough for picking faces. Same numbers on every platform.
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        splitmix(self.0)
+    }
+
+    /// Evenly spread between -max and max.
+    fn between(&mut self, max: f32) -> f32 {
+        let unit = (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+        (unit * 2.0 - 1.0) * max
+    }
+}
+
+fn splitmix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
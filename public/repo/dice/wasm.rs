@@ -0,0 +1,153 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Uint8Array;
+
+use crate::dicelib::DiceGrid;
+use crate::encode::OutputFormat;
+use crate::error::DiceError;
+use crate::metadata::read_settings;
+use crate::profile::Profile;
+use crate::progress::Hooks;
+use crate::renderer::{DiceRenderer, RenderOptions};
+
+/// Errors reach JS as an `Error` named after the kind, e.g. `InvalidFaceSetError`,
+/// so the page can tell a bad face set from a photo it can't read.
+impl From<DiceError> for JsValue {
+    fn from(err: DiceError) -> JsValue {
+        let js = js_sys::Error::new(&err.to_string());
+        js.set_name(err.kind());
+        js.into()
+    }
+}
+
+/// Settings from JS, in the profile layout the CLI saves: a plain object with the profile's keys
+/// (`{ "dice-size": 16, preset: "bright", seed: 42 }`), or profile text, JSON or TOML, of any version.
+/// `undefined` and `null` leave everything at its default. Anything `settings_schema()` would turn down throws.
+fn profile(settings: &JsValue) -> Result<Profile, DiceError> {
+    if settings.is_undefined() || settings.is_null() {
+        return Ok(Profile::default());
+    }
+    if let Some(text) = settings.as_string() {
+        return Profile::from_text(&text);
+    }
+    let json = js_sys::JSON::stringify(settings)
+        .ok()
+        .and_then(|json| json.as_string())
+        .ok_or_else(|| DiceError::Decode("Bad profile: settings have to be an object or profile text".to_string()))?;
+    Profile::from_json(&json)
+}
+
+/// A profile as a plain JS object, the same keys it's read with.
+fn to_js(profile: &Profile) -> Result<JsValue, DiceError> {
+    js_sys::JSON::parse(&profile.to_json()).map_err(|_| DiceError::Encode("profile didn't come back as JSON".to_string()))
+}
+
+/// The options the pipeline takes for these settings.
+fn render_options(profile: &Profile) -> Result<RenderOptions, DiceError> {
+    let mut options = profile.render_options()?;
+    // A faster AVIF encode than the CLI's, the page is waiting on it
+    if let OutputFormat::Avif { speed, .. } = &mut options.format {
+        *speed = 8;
+    }
+    Ok(options)
+}
+
+/// Every setting spelled out, the way a render fills in what `settings` leaves out. Save it with
+/// `JSON.stringify` and the CLI loads it with `--config`, or put it in a settings form.
+#[wasm_bindgen]
+pub fn resolve_settings(settings: JsValue) -> Result<JsValue, JsValue> {
+    Ok(to_js(&profile(&settings)?.with_defaults()?)?)
+}
+
+/// MIME type of the bytes process_dice_image returns for these settings, e.g. for the Blob.
+#[wasm_bindgen]
+pub fn output_mime_type(settings: JsValue) -> Result<String, JsValue> {
+    Ok(render_options(&profile(&settings)?)?.format.mime_type().to_string())
+}
+
+/// File extension matching the output format of these settings, e.g. for the download name.
+#[wasm_bindgen]
+pub fn output_extension(settings: JsValue) -> Result<String, JsValue> {
+    Ok(render_options(&profile(&settings)?)?.format.extension().to_string())
+}
+
+/// A renderer with the 6 faces from a JS Array of Uint8Arrays, One first.
+fn renderer(dice_pngs: &js_sys::Array, settings: &JsValue) -> Result<DiceRenderer, DiceError> {
+    let faces: Vec<Vec<u8>> = dice_pngs.iter().map(|face| Uint8Array::new(&face).to_vec()).collect();
+    DiceRenderer::from_face_bytes(render_options(&profile(settings)?)?, &faces)
+}
+
+/// input_bytes: original image (PNG/JPEG/etc)
+/// dice_pngs:   JS Array of 6 Uint8Array dice images (faces 1..6, your order)
+/// settings:    an object with the profile keys, or profile text, JSON or TOML (undefined for the defaults)
+/// on_progress: optional, called as `(stage, done, total)` with stage "analysing", "rendering" or "encoding"
+///              and done / total in rows of dice
+/// should_abort: optional, asked between rows; returning true stops with an `AbortError`. The call is
+///              synchronous, so run it in a worker and read a flag the page can set meanwhile, e.g.
+///              `() => Atomics.load(flag, 0) !== 0` on a SharedArrayBuffer (the playground does this)
+/// returns:     the mosaic encoded in the settings' format (PNG unless changed)
+#[wasm_bindgen]
+pub fn process_dice_image(
+    input_bytes: Uint8Array,
+    dice_pngs: js_sys::Array,
+    settings: JsValue,
+    on_progress: Option<js_sys::Function>,
+    should_abort: Option<js_sys::Function>,
+) -> Result<Uint8Array, JsValue> {
+    console_error_panic_hook::set_once();
+
+    let mut hooks = Hooks::none();
+    if let Some(f) = on_progress {
+        // A throwing callback shouldn't cost the render, its exception is dropped
+        hooks = hooks.on_progress(move |p| {
+            let _ = f.call3(&JsValue::NULL, &p.stage.name().into(), &p.done.into(), &p.total.into());
+        });
+    }
+    if let Some(f) = should_abort {
+        hooks = hooks.stop_when(move || f.call0(&JsValue::NULL).is_ok_and(|v| v.is_truthy()));
+    }
+    let bytes = renderer(&dice_pngs, &settings)?.process(&input_bytes.to_vec(), &mut hooks)?;
+    Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+/// Analysis only: returns the grid as DICEGRID text, to store and re-render with render_dice_grid.
+/// Uses the dice size, inversion of the photo, preset or thresholds and output size from the settings.
+#[wasm_bindgen]
+pub fn analyze_dice_image(input_bytes: Uint8Array, settings: JsValue) -> Result<String, JsValue> {
+    console_error_panic_hook::set_once();
+
+    let renderer = DiceRenderer::without_faces(render_options(&profile(&settings)?)?);
+    let grid = renderer.grid_for(&input_bytes.to_vec(), &mut Hooks::none())?;
+    Ok(grid.to_text())
+}
+
+/// Renders a saved grid with any face set, dice size, gap and output format,
+/// without going back to the original photo.
+#[wasm_bindgen]
+pub fn render_dice_grid(
+    grid: &str,
+    dice_pngs: js_sys::Array,
+    settings: JsValue,
+) -> Result<Uint8Array, JsValue> {
+    console_error_panic_hook::set_once();
+
+    let grid = DiceGrid::from_text(grid)?;
+    let renderer = renderer(&dice_pngs, &settings)?;
+    let bytes = renderer.encode_grid(&grid, true, &mut Hooks::none())?;
+
+    Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+/// Reads the settings embedded in a PNG made by process_dice_image (or the CLI),
+/// as a settings object, so it can be rendered again exactly, or again with a few tweaks.
+#[wasm_bindgen]
+pub fn read_dice_options(png_bytes: Uint8Array) -> Result<JsValue, JsValue> {
+    let settings = read_settings(&png_bytes.to_vec())?;
+    Ok(to_js(&Profile::from_settings(&settings))?)
+}
+
+/// JSON Schema of the settings every function here takes (and the CLI's profiles),
+/// to check a settings form or a queued job before it's sent.
+#[wasm_bindgen]
+pub fn settings_schema() -> String {
+    Profile::json_schema()
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How often watched files get looked at.
+pub const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+type Stamp = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+/// A file or directory checked for changes by polling modification times and sizes.
+/// No OS notification APIs, so it behaves the same everywhere (network drives included).
+pub struct Watched {
+    path: PathBuf,
+    stamp: Stamp,
+}
+
+impl Watched {
+    pub fn new(path: impl Into<PathBuf>) -> Watched {
+        let path = path.into();
+        let stamp = stamp(&path);
+        Watched { path, stamp }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True if anything changed since the last call (or since `new`).
+    pub fn changed(&mut self) -> bool {
+        let now = stamp(&self.path);
+        if now == self.stamp {
+            return false;
+        }
+        self.stamp = now;
+        true
+    }
+}
+
+/// Modification time and size of a file, or of every file in a directory.
+fn stamp(path: &Path) -> Stamp {
+    let entry = |p: PathBuf| {
+        let meta = std::fs::metadata(&p).ok();
+        let modified = meta.as_ref().and_then(|m| m.modified().ok());
+        let len = meta.map_or(0, |m| m.len());
+        (p, modified, len)
+    };
+
+    if path.is_dir() {
+        let mut entries: Stamp = std::fs::read_dir(path)
+            .map(|dir| dir.filter_map(|e| e.ok()).map(|e| entry(e.path())).collect())
+            .unwrap_or_default();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    } else {
+        vec![entry(path.to_path_buf())]
+    }
+}
@@ -1,99 +1,99 @@
 /* tslint:disable */
 /* eslint-disable */
+
+/**
+ * Analysis only: returns the grid as DICEGRID text, to store and re-render with render_dice_grid.
+ * Uses the dice size, inversion of the photo, preset or thresholds and output size from the settings.
+ */
+export function analyze_dice_image(input_bytes: Uint8Array, settings: any): string;
+
+/**
+ * File extension matching the output format of these settings, e.g. for the download name.
+ */
+export function output_extension(settings: any): string;
+
+/**
+ * MIME type of the bytes process_dice_image returns for these settings, e.g. for the Blob.
+ */
+export function output_mime_type(settings: any): string;
+
 /**
  * input_bytes: original image (PNG/JPEG/etc)
  * dice_pngs:   JS Array of 6 Uint8Array dice images (faces 1..6, your order)
+ * settings:    an object with the profile keys, or profile text, JSON or TOML (undefined for the defaults)
+ * on_progress: optional, called as `(stage, done, total)` with stage "analysing", "rendering" or "encoding"
+ *              and done / total in rows of dice
+ * should_abort: optional, asked between rows; returning true stops with an `AbortError`. The call is
+ *              synchronous, so run it in a worker and read a flag the page can set meanwhile, e.g.
+ *              `() => Atomics.load(flag, 0) !== 0` on a SharedArrayBuffer (the playground does this)
+ * returns:     the mosaic encoded in the settings' format (PNG unless changed)
  */
-export function process_dice_image(input_bytes: Uint8Array, dice_pngs: Array<any>, opts: DiceOptions): Uint8Array;
+export function process_dice_image(input_bytes: Uint8Array, dice_pngs: Array<any>, settings: any, on_progress?: Function | null, should_abort?: Function | null): Uint8Array;
+
 /**
- * Chroma subsampling format
+ * Reads the settings embedded in a PNG made by process_dice_image (or the CLI),
+ * as a settings object, so it can be rendered again exactly, or again with a few tweaks.
  */
-export enum ChromaSampling {
-  /**
-   * Both vertically and horizontally subsampled.
-   */
-  Cs420 = 0,
-  /**
-   * Horizontally subsampled.
-   */
-  Cs422 = 1,
-  /**
-   * Not subsampled.
-   */
-  Cs444 = 2,
-  /**
-   * Monochrome.
-   */
-  Cs400 = 3,
-}
-export enum IntensityPreset {
-  Default = 0,
-  HighContrast = 1,
-  LowContrast = 2,
-  Bright = 3,
-  Dark = 4,
-}
-export class DiceOptions {
-  free(): void;
-  [Symbol.dispose](): void;
-  constructor(dice_size: number, invert_input: boolean, invert_dice: boolean, preset: IntensityPreset, output_width: number | null | undefined, output_height: number | null | undefined, add_debug: boolean);
-  dice_size: number;
-  invert_input: boolean;
-  invert_dice: boolean;
-  preset: IntensityPreset;
-  get output_width(): number | undefined;
-  set output_width(value: number | null | undefined);
-  get output_height(): number | undefined;
-  set output_height(value: number | null | undefined);
-  add_debug: boolean;
-}
+export function read_dice_options(png_bytes: Uint8Array): any;
+
+/**
+ * Renders a saved grid with any face set, dice size, gap and output format,
+ * without going back to the original photo.
+ */
+export function render_dice_grid(grid: string, dice_pngs: Array<any>, settings: any): Uint8Array;
+
+/**
+ * Every setting spelled out, the way a render fills in what `settings` leaves out. Save it with
+ * `JSON.stringify` and the CLI loads it with `--config`, or put it in a settings form.
+ */
+export function resolve_settings(settings: any): any;
+
+/**
+ * JSON Schema of the settings every function here takes (and the CLI's profiles),
+ * to check a settings form or a queued job before it's sent.
+ */
+export function settings_schema(): string;
 
 export type InitInput = RequestInfo | URL | Response | BufferSource | WebAssembly.Module;
 
 export interface InitOutput {
-  readonly memory: WebAssembly.Memory;
-  readonly __wbg_diceoptions_free: (a: number, b: number) => void;
-  readonly __wbg_get_diceoptions_add_debug: (a: number) => number;
-  readonly __wbg_get_diceoptions_dice_size: (a: number) => number;
-  readonly __wbg_get_diceoptions_invert_dice: (a: number) => number;
-  readonly __wbg_get_diceoptions_invert_input: (a: number) => number;
-  readonly __wbg_get_diceoptions_output_height: (a: number) => number;
-  readonly __wbg_get_diceoptions_output_width: (a: number) => number;
-  readonly __wbg_get_diceoptions_preset: (a: number) => number;
-  readonly __wbg_set_diceoptions_add_debug: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_dice_size: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_invert_dice: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_invert_input: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_output_height: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_output_width: (a: number, b: number) => void;
-  readonly __wbg_set_diceoptions_preset: (a: number, b: number) => void;
-  readonly diceoptions_new: (a: number, b: number, c: number, d: number, e: number, f: number, g: number) => number;
-  readonly process_dice_image: (a: any, b: any, c: number) => [number, number, number];
-  readonly __wbindgen_free: (a: number, b: number, c: number) => void;
-  readonly __wbindgen_malloc: (a: number, b: number) => number;
-  readonly __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
-  readonly __wbindgen_externrefs: WebAssembly.Table;
-  readonly __externref_table_dealloc: (a: number) => void;
-  readonly __wbindgen_start: () => void;
+    readonly memory: WebAssembly.Memory;
+    readonly analyze_dice_image: (a: any, b: any) => [number, number, number, number];
+    readonly output_extension: (a: any) => [number, number, number, number];
+    readonly output_mime_type: (a: any) => [number, number, number, number];
+    readonly process_dice_image: (a: any, b: any, c: any, d: number, e: number) => [number, number, number];
+    readonly read_dice_options: (a: any) => [number, number, number];
+    readonly render_dice_grid: (a: number, b: number, c: any, d: any) => [number, number, number];
+    readonly resolve_settings: (a: any) => [number, number, number];
+    readonly settings_schema: () => [number, number];
+    readonly __wbindgen_malloc: (a: number, b: number) => number;
+    readonly __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
+    readonly __wbindgen_exn_store: (a: number) => void;
+    readonly __externref_table_alloc: () => number;
+    readonly __wbindgen_externrefs: WebAssembly.Table;
+    readonly __wbindgen_free: (a: number, b: number, c: number) => void;
+    readonly __externref_table_dealloc: (a: number) => void;
+    readonly __wbindgen_start: () => void;
 }
 
 export type SyncInitInput = BufferSource | WebAssembly.Module;
+
 /**
-* Instantiates the given `module`, which can either be bytes or
-* a precompiled `WebAssembly.Module`.
-*
-* @param {{ module: SyncInitInput }} module - Passing `SyncInitInput` directly is deprecated.
-*
-* @returns {InitOutput}
-*/
+ * Instantiates the given `module`, which can either be bytes or
+ * a precompiled `WebAssembly.Module`.
+ *
+ * @param {{ module: SyncInitInput }} module - Passing `SyncInitInput` directly is deprecated.
+ *
+ * @returns {InitOutput}
+ */
 export function initSync(module: { module: SyncInitInput } | SyncInitInput): InitOutput;
 
 /**
-* If `module_or_path` is {RequestInfo} or {URL}, makes a request and
-* for everything else, calls `WebAssembly.instantiate` directly.
-*
-* @param {{ module_or_path: InitInput | Promise<InitInput> }} module_or_path - Passing `InitInput` directly is deprecated.
-*
-* @returns {Promise<InitOutput>}
-*/
+ * If `module_or_path` is {RequestInfo} or {URL}, makes a request and
+ * for everything else, calls `WebAssembly.instantiate` directly.
+ *
+ * @param {{ module_or_path: InitInput | Promise<InitInput> }} module_or_path - Passing `InitInput` directly is deprecated.
+ *
+ * @returns {Promise<InitOutput>}
+ */
 export default function __wbg_init (module_or_path?: { module_or_path: InitInput | Promise<InitInput> } | InitInput | Promise<InitInput>): Promise<InitOutput>;
@@ -1,26 +1,19 @@
 /* tslint:disable */
 /* eslint-disable */
 export const memory: WebAssembly.Memory;
-export const __wbg_diceoptions_free: (a: number, b: number) => void;
-export const __wbg_get_diceoptions_add_debug: (a: number) => number;
-export const __wbg_get_diceoptions_dice_size: (a: number) => number;
-export const __wbg_get_diceoptions_invert_dice: (a: number) => number;
-export const __wbg_get_diceoptions_invert_input: (a: number) => number;
-export const __wbg_get_diceoptions_output_height: (a: number) => number;
-export const __wbg_get_diceoptions_output_width: (a: number) => number;
-export const __wbg_get_diceoptions_preset: (a: number) => number;
-export const __wbg_set_diceoptions_add_debug: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_dice_size: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_invert_dice: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_invert_input: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_output_height: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_output_width: (a: number, b: number) => void;
-export const __wbg_set_diceoptions_preset: (a: number, b: number) => void;
-export const diceoptions_new: (a: number, b: number, c: number, d: number, e: number, f: number, g: number) => number;
-export const process_dice_image: (a: any, b: any, c: number) => [number, number, number];
-export const __wbindgen_free: (a: number, b: number, c: number) => void;
+export const analyze_dice_image: (a: any, b: any) => [number, number, number, number];
+export const output_extension: (a: any) => [number, number, number, number];
+export const output_mime_type: (a: any) => [number, number, number, number];
+export const process_dice_image: (a: any, b: any, c: any, d: number, e: number) => [number, number, number];
+export const read_dice_options: (a: any) => [number, number, number];
+export const render_dice_grid: (a: number, b: number, c: any, d: any) => [number, number, number];
+export const resolve_settings: (a: any) => [number, number, number];
+export const settings_schema: () => [number, number];
 export const __wbindgen_malloc: (a: number, b: number) => number;
 export const __wbindgen_realloc: (a: number, b: number, c: number, d: number) => number;
+export const __wbindgen_exn_store: (a: number) => void;
+export const __externref_table_alloc: () => number;
 export const __wbindgen_externrefs: WebAssembly.Table;
+export const __wbindgen_free: (a: number, b: number, c: number) => void;
 export const __externref_table_dealloc: (a: number) => void;
 export const __wbindgen_start: () => void;